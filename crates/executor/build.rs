@@ -1,13 +1,11 @@
-use std::env;
-
 fn main() {
-    // Only enable SIMD features on x86_64 architecture
-    if env::var("TARGET").unwrap_or_default().contains("x86_64") {
-        // Enable configuration for SIMD instruction sets
-        println!("cargo:rustc-cfg=target_feature=\"sse2\"");
-        println!("cargo:rustc-cfg=target_feature=\"avx2\"");
-    }
-    
+    // `utils_simd` dispatches between AVX2/SSE2/scalar at runtime via
+    // `is_x86_feature_detected!`, since the CPU that runs the binary isn't
+    // necessarily the one it was compiled on. We used to force
+    // `target_feature = "avx2"` on for every x86_64 target here, which made
+    // the compiler assume AVX2 was always available and could miscompile for
+    // older CPUs; there is nothing left for this build script to configure.
+
     // Rerun the build script if it changes
     println!("cargo:rerun-if-changed=build.rs");
-} 
\ No newline at end of file
+}
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sp1_core_executor::fuzz::{run_differential, FuzzProgram};
+
+fuzz_target!(|program: FuzzProgram| {
+    if let Err(divergence) = run_differential(program) {
+        eprintln!("executor/oracle diverged: {divergence:?}");
+        panic!("differential fuzzing found a mismatch");
+    }
+});
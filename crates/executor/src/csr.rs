@@ -0,0 +1,413 @@
+//! Machine-mode control/status registers, trap-taking, and a minimal
+//! software-triggerable interrupt controller.
+//!
+//! This gives programs that install their own trap handlers (via `mtvec`) a
+//! way to actually run instead of the executor aborting on the first illegal
+//! instruction, misaligned access, or `ECALL`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::ExecutionState;
+
+/// Address of `mstatus`.
+pub const CSR_MSTATUS: u32 = 0x300;
+/// Address of `mie`.
+pub const CSR_MIE: u32 = 0x304;
+/// Address of `mtvec`.
+pub const CSR_MTVEC: u32 = 0x305;
+/// Address of `mepc`.
+pub const CSR_MEPC: u32 = 0x341;
+/// Address of `mcause`.
+pub const CSR_MCAUSE: u32 = 0x342;
+/// Address of `mtval`.
+pub const CSR_MTVAL: u32 = 0x343;
+/// Address of `mip`.
+pub const CSR_MIP: u32 = 0x344;
+/// Address of `fcsr`.
+pub const CSR_FCSR: u32 = 0x003;
+
+/// Bit position of `mstatus.MIE` (global machine-mode interrupt enable).
+const MSTATUS_MIE_BIT: u32 = 3;
+/// Bit position of `mstatus.MPIE` (previous value of `MIE`, saved on trap).
+const MSTATUS_MPIE_BIT: u32 = 7;
+
+/// The machine-mode CSR file, stored alongside the register arrays in
+/// [`ExecutionState`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CsrFile {
+    /// Machine status register.
+    pub mstatus: u32,
+    /// Machine interrupt-enable register: one bit per interrupt source.
+    pub mie: u32,
+    /// Machine trap vector: handler address, with the low 2 bits selecting
+    /// direct (`0b00`) or vectored (`0b01`) mode.
+    pub mtvec: u32,
+    /// Machine exception program counter: `pc` at the time of the trap.
+    pub mepc: u32,
+    /// Machine trap cause: bit 31 set for interrupts, exception code in the
+    /// low bits otherwise.
+    pub mcause: u32,
+    /// Machine trap value: faulting address or instruction, as appropriate.
+    pub mtval: u32,
+    /// Machine interrupt-pending register: one bit per interrupt source.
+    pub mip: u32,
+    /// Floating-point control/status register (`frm` + accrued flags); see
+    /// the `F`/`D` extension support for how this is consumed.
+    pub fcsr: u32,
+}
+
+/// `fcsr` bit for the invalid-operation accrued exception flag.
+pub const FFLAG_NV: u32 = 1 << 4;
+/// `fcsr` bit for the divide-by-zero accrued exception flag.
+pub const FFLAG_DZ: u32 = 1 << 3;
+/// `fcsr` bit for the overflow accrued exception flag.
+pub const FFLAG_OF: u32 = 1 << 2;
+/// `fcsr` bit for the underflow accrued exception flag.
+pub const FFLAG_UF: u32 = 1 << 1;
+/// `fcsr` bit for the inexact accrued exception flag.
+pub const FFLAG_NX: u32 = 1;
+
+impl CsrFile {
+    /// The dynamic rounding mode (`fcsr.frm`, bits `[7:5]`).
+    #[must_use]
+    pub fn frm(&self) -> u32 {
+        (self.fcsr >> 5) & 0x7
+    }
+
+    /// The accrued floating-point exception flags (`fcsr.fflags`, bits `[4:0]`).
+    #[must_use]
+    pub fn fflags(&self) -> u32 {
+        self.fcsr & 0x1F
+    }
+
+    /// OR `flags` (e.g. [`FFLAG_NX`]) into the accrued exception flags. Per
+    /// the RISC-V spec, `fflags` accumulates and is never cleared by an
+    /// operation, only by an explicit CSR write.
+    pub fn accrue_fflags(&mut self, flags: u32) {
+        self.fcsr |= flags & 0x1F;
+    }
+
+    /// Read the CSR at `addr`, or `None` if it is not implemented.
+    #[must_use]
+    pub fn read(&self, addr: u32) -> Option<u32> {
+        Some(match addr {
+            CSR_MSTATUS => self.mstatus,
+            CSR_MIE => self.mie,
+            CSR_MTVEC => self.mtvec,
+            CSR_MEPC => self.mepc,
+            CSR_MCAUSE => self.mcause,
+            CSR_MTVAL => self.mtval,
+            CSR_MIP => self.mip,
+            CSR_FCSR => self.fcsr,
+            _ => return None,
+        })
+    }
+
+    /// Write `value` to the CSR at `addr`. Returns `false` if `addr` is not
+    /// implemented, in which case the caller should raise an illegal
+    /// instruction trap.
+    #[must_use]
+    pub fn write(&mut self, addr: u32, value: u32) -> bool {
+        match addr {
+            CSR_MSTATUS => self.mstatus = value,
+            CSR_MIE => self.mie = value,
+            CSR_MTVEC => self.mtvec = value,
+            CSR_MEPC => self.mepc = value,
+            CSR_MCAUSE => self.mcause = value,
+            CSR_MTVAL => self.mtval = value,
+            CSR_MIP => self.mip = value,
+            CSR_FCSR => self.fcsr = value,
+            _ => return false,
+        }
+        true
+    }
+
+    fn mstatus_bit(&self, bit: u32) -> bool {
+        (self.mstatus >> bit) & 1 == 1
+    }
+
+    fn set_mstatus_bit(&mut self, bit: u32, value: bool) {
+        if value {
+            self.mstatus |= 1 << bit;
+        } else {
+            self.mstatus &= !(1 << bit);
+        }
+    }
+}
+
+/// The reason execution is trapping into the handler installed at `mtvec`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapCause {
+    /// The decoder could not recognize the instruction word, or it encoded
+    /// something that is not legal in this mode (e.g. an invalid rounding
+    /// mode on a floating-point op).
+    IllegalInstruction,
+    /// A load's effective address was not naturally aligned.
+    LoadAddressMisaligned,
+    /// A store's effective address was not naturally aligned.
+    StoreAddressMisaligned,
+    /// `pc` itself was not 4-byte aligned after a branch or jump.
+    InstructionAddressMisaligned,
+    /// An `ECALL` was executed from machine mode.
+    EnvironmentCallFromMMode,
+    /// A pending, enabled interrupt identified by its bit position in
+    /// `mip`/`mie` (e.g. `7` for the machine timer interrupt).
+    MachineInterrupt(u32),
+}
+
+impl TrapCause {
+    /// The `mcause` encoding for this trap: bit 31 set and the interrupt
+    /// number in the low bits for interrupts, the exception code otherwise.
+    #[must_use]
+    pub fn mcause(self) -> u32 {
+        match self {
+            TrapCause::IllegalInstruction => 2,
+            TrapCause::InstructionAddressMisaligned => 0,
+            TrapCause::EnvironmentCallFromMMode => 11,
+            TrapCause::LoadAddressMisaligned => 4,
+            TrapCause::StoreAddressMisaligned => 6,
+            TrapCause::MachineInterrupt(bit) => (1 << 31) | bit,
+        }
+    }
+}
+
+impl ExecutionState {
+    /// Take a trap: save `pc` into `mepc`, record `cause`/`tval` in
+    /// `mcause`/`mtval`, clear `mstatus.MIE` (saving its old value into
+    /// `MPIE`), and jump to the handler at `mtvec`.
+    pub fn take_trap(&mut self, cause: TrapCause, tval: u32) {
+        self.csrs.mepc = self.pc;
+        self.csrs.mcause = cause.mcause();
+        self.csrs.mtval = tval;
+
+        let mie_was_set = self.csrs.mstatus_bit(MSTATUS_MIE_BIT);
+        self.csrs.set_mstatus_bit(MSTATUS_MPIE_BIT, mie_was_set);
+        self.csrs.set_mstatus_bit(MSTATUS_MIE_BIT, false);
+
+        // Direct mode (low 2 bits `0b00`): always jump to the base address.
+        // Vectored mode (`0b01`): jump to `base + 4 * cause` for interrupts.
+        let base = self.csrs.mtvec & !0b11;
+        self.pc = if self.csrs.mtvec & 0b11 == 0b01 && cause.mcause() & (1 << 31) != 0 {
+            base.wrapping_add(4 * (cause.mcause() & !(1 << 31)))
+        } else {
+            base
+        };
+    }
+
+    /// Check for a pending, enabled interrupt and, if one is found, take it.
+    ///
+    /// The executor calls this between instruction retirements rather than
+    /// mid-instruction, matching how a generic machine-mode interrupt
+    /// controller raises interrupts at an instruction boundary.
+    pub fn poll_interrupts(&mut self) -> bool {
+        if !self.csrs.mstatus_bit(MSTATUS_MIE_BIT) {
+            return false;
+        }
+
+        let pending = self.csrs.mip & self.csrs.mie;
+        if pending == 0 {
+            return false;
+        }
+
+        // Lowest-numbered pending+enabled bit is taken first.
+        let bit = pending.trailing_zeros();
+        self.take_trap(TrapCause::MachineInterrupt(bit), 0);
+        true
+    }
+
+    /// Raise `mip`'s bit `bit`, marking that interrupt source pending. The
+    /// interrupt is not taken until the next [`ExecutionState::poll_interrupts`]
+    /// call observes it enabled in `mie`.
+    pub fn set_interrupt_pending(&mut self, bit: u32, pending: bool) {
+        if pending {
+            self.csrs.mip |= 1 << bit;
+        } else {
+            self.csrs.mip &= !(1 << bit);
+        }
+    }
+}
+
+/// The `csrrw`/`csrrs`/`csrrc` family of opcodes, decoded from a `SYSTEM`
+/// (`0b1110011`) instruction word with a non-zero `funct3`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsrOp {
+    /// `csrrw`/`csrrwi`: unconditionally swap in the new value.
+    ReadWrite,
+    /// `csrrs`/`csrrsi`: set the bits in `rs1`/`uimm`.
+    ReadSet,
+    /// `csrrc`/`csrrci`: clear the bits in `rs1`/`uimm`.
+    ReadClear,
+}
+
+/// The source of the value written to a CSR: either a register or a 5-bit
+/// immediate (the `*i` variants).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsrOperand {
+    /// `rs1`, read as a register index.
+    Register(usize),
+    /// A zero-extended 5-bit immediate (`csrrwi`/`csrrsi`/`csrrci`).
+    Immediate(u32),
+}
+
+/// A decoded CSR instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsrInstruction {
+    /// The CSR address (`imm[31:20]` of the instruction word).
+    pub csr: u32,
+    /// The destination register (`rd`); `x0` means the old value is
+    /// discarded.
+    pub rd: usize,
+    /// The source of the new value.
+    pub operand: CsrOperand,
+    /// Which read-modify-write flavor this is.
+    pub op: CsrOp,
+}
+
+/// Decode `word` as a `csrrw`/`csrrs`/`csrrc` (+ immediate variants)
+/// instruction. Returns `None` for any other `SYSTEM`-opcode instruction
+/// (e.g. `ECALL`/`EBREAK`, where `funct3 == 0`), which the caller should
+/// handle separately.
+#[must_use]
+pub fn decode_csr(word: u32) -> Option<CsrInstruction> {
+    const SYSTEM_OPCODE: u32 = 0b1110011;
+
+    let opcode = word & 0x7F;
+    if opcode != SYSTEM_OPCODE {
+        return None;
+    }
+
+    let funct3 = (word >> 12) & 0x7;
+    let rd = ((word >> 7) & 0x1F) as usize;
+    let rs1 = (word >> 15) & 0x1F;
+    let csr = word >> 20;
+
+    let op = match funct3 & 0b011 {
+        0b001 => CsrOp::ReadWrite,
+        0b010 => CsrOp::ReadSet,
+        0b011 => CsrOp::ReadClear,
+        _ => return None,
+    };
+    let operand =
+        if funct3 & 0b100 != 0 { CsrOperand::Immediate(rs1) } else { CsrOperand::Register(rs1 as usize) };
+
+    Some(CsrInstruction { csr, rd, operand, op })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_trap_direct_mode_always_jumps_to_the_base() {
+        let mut state = ExecutionState::new(0x1000);
+        state.csrs.mtvec = 0x8000; // low 2 bits 0b00: direct mode
+
+        state.take_trap(TrapCause::IllegalInstruction, 0xDEAD_BEEF);
+
+        assert_eq!(state.pc, 0x8000);
+        assert_eq!(state.csrs.mepc, 0x1000);
+        assert_eq!(state.csrs.mcause, TrapCause::IllegalInstruction.mcause());
+        assert_eq!(state.csrs.mtval, 0xDEAD_BEEF);
+    }
+
+    #[test]
+    fn take_trap_vectored_mode_offsets_interrupts_by_four_times_the_cause() {
+        let mut state = ExecutionState::new(0x1000);
+        state.csrs.mtvec = 0x8000 | 0b01; // vectored mode
+
+        state.take_trap(TrapCause::MachineInterrupt(7), 0);
+
+        assert_eq!(state.pc, 0x8000 + 4 * 7);
+    }
+
+    #[test]
+    fn take_trap_vectored_mode_does_not_offset_exceptions() {
+        let mut state = ExecutionState::new(0x1000);
+        state.csrs.mtvec = 0x8000 | 0b01; // vectored mode
+
+        state.take_trap(TrapCause::IllegalInstruction, 0);
+
+        // Only interrupts (mcause bit 31 set) are vectored; exceptions still
+        // jump straight to the base.
+        assert_eq!(state.pc, 0x8000);
+    }
+
+    #[test]
+    fn take_trap_disables_mie_and_saves_it_into_mpie() {
+        let mut state = ExecutionState::new(0);
+        state.csrs.mstatus = 1 << MSTATUS_MIE_BIT;
+
+        state.take_trap(TrapCause::EnvironmentCallFromMMode, 0);
+
+        assert!(!state.csrs.mstatus_bit(MSTATUS_MIE_BIT));
+        assert!(state.csrs.mstatus_bit(MSTATUS_MPIE_BIT));
+    }
+
+    #[test]
+    fn poll_interrupts_does_nothing_when_mie_is_clear() {
+        let mut state = ExecutionState::new(0x1000);
+        state.csrs.mip = 1;
+        state.csrs.mie = 1;
+        // mstatus.MIE left clear.
+
+        assert!(!state.poll_interrupts());
+        assert_eq!(state.pc, 0x1000);
+    }
+
+    #[test]
+    fn poll_interrupts_does_nothing_when_nothing_is_both_pending_and_enabled() {
+        let mut state = ExecutionState::new(0x1000);
+        state.csrs.mstatus = 1 << MSTATUS_MIE_BIT;
+        state.csrs.mip = 1 << 3;
+        state.csrs.mie = 1 << 5; // disjoint from mip
+
+        assert!(!state.poll_interrupts());
+        assert_eq!(state.pc, 0x1000);
+    }
+
+    #[test]
+    fn poll_interrupts_takes_the_lowest_numbered_pending_enabled_bit() {
+        let mut state = ExecutionState::new(0x1000);
+        state.csrs.mstatus = 1 << MSTATUS_MIE_BIT;
+        state.csrs.mtvec = 0x8000; // direct mode
+        state.csrs.mip = (1 << 7) | (1 << 3) | (1 << 11);
+        state.csrs.mie = (1 << 7) | (1 << 3) | (1 << 11);
+
+        assert!(state.poll_interrupts());
+        assert_eq!(state.csrs.mcause, TrapCause::MachineInterrupt(3).mcause());
+        assert_eq!(state.pc, 0x8000);
+    }
+
+    #[test]
+    fn set_interrupt_pending_sets_and_clears_the_mip_bit() {
+        let mut state = ExecutionState::new(0);
+
+        state.set_interrupt_pending(5, true);
+        assert_eq!(state.csrs.mip, 1 << 5);
+
+        state.set_interrupt_pending(5, false);
+        assert_eq!(state.csrs.mip, 0);
+    }
+
+    #[test]
+    fn decode_csr_distinguishes_register_and_immediate_operands() {
+        // csrrw x1, mstatus, x2
+        let csrrw = (CSR_MSTATUS << 20) | (2 << 15) | (0b001 << 12) | (1 << 7) | 0b1110011;
+        let decoded = decode_csr(csrrw).expect("csrrw is a CSR instruction");
+        assert_eq!(decoded.csr, CSR_MSTATUS);
+        assert_eq!(decoded.rd, 1);
+        assert_eq!(decoded.op, CsrOp::ReadWrite);
+        assert_eq!(decoded.operand, CsrOperand::Register(2));
+
+        // csrrsi x1, mstatus, 5
+        let csrrsi = (CSR_MSTATUS << 20) | (5 << 15) | (0b110 << 12) | (1 << 7) | 0b1110011;
+        let decoded = decode_csr(csrrsi).expect("csrrsi is a CSR instruction");
+        assert_eq!(decoded.op, CsrOp::ReadSet);
+        assert_eq!(decoded.operand, CsrOperand::Immediate(5));
+    }
+
+    #[test]
+    fn decode_csr_rejects_non_system_opcodes() {
+        assert_eq!(decode_csr(0b0110011), None);
+    }
+}
@@ -0,0 +1,278 @@
+//! The fetch-decode-execute loop tying [`Instruction::decode`]'s output to
+//! [`ExecutionState`].
+//!
+//! This is what gives `csr::decode_csr`/`take_trap`/`poll_interrupts` and
+//! `fpu::decode_fp`/`execute_fp` a real caller instead of just their own
+//! unit tests, and what lets a program that installs a trap handler (via
+//! `mtvec`) actually run instead of the first illegal instruction,
+//! misaligned access, or `ECALL` aborting execution outright.
+
+use crate::events::MemoryRecord;
+use crate::{
+    decode, AluImmOp, AluOp, BranchOp, CsrInstruction, CsrOp, CsrOperand, ExecutionState,
+    ExecutorMode, Instruction, Program, TrapCause,
+};
+
+/// Ties a [`Program`] to the [`ExecutionState`] it runs against.
+pub struct Executor {
+    /// The machine state the program executes against.
+    pub state: ExecutionState,
+    /// Whether the executor is tracing/emitting events; threaded through
+    /// fork/restore by [`crate::fork`].
+    pub executor_mode: ExecutorMode,
+    program: Program,
+}
+
+impl Executor {
+    /// Build an [`Executor`] for `program`, with `state` starting at
+    /// `program.pc_start`.
+    #[must_use]
+    pub fn new(program: Program) -> Self {
+        Self {
+            state: ExecutionState::new(program.pc_start),
+            executor_mode: ExecutorMode::default(),
+            program,
+        }
+    }
+
+    /// Run until `pc` leaves the program's bounds, an unhandled trap is
+    /// taken, or `max_cycles` instructions have retired, whichever comes
+    /// first.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`TrapCause`] of a trap taken with no handler installed
+    /// (`mtvec == 0`). Without this, a program that never installs a trap
+    /// handler would otherwise have the executor jump to address `0` and
+    /// either execute whatever happens to be loaded there or trap again
+    /// forever.
+    pub fn run(&mut self, max_cycles: u64) -> Result<(), TrapCause> {
+        let end = self.program.text_end();
+        while self.state.global_clk < max_cycles && self.state.pc < end {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<(), TrapCause> {
+        if self.state.poll_interrupts() {
+            self.state.global_clk += 1;
+            return Ok(());
+        }
+
+        if self.state.pc % 4 != 0 {
+            return self.trap(TrapCause::InstructionAddressMisaligned, self.state.pc);
+        }
+
+        let index = ((self.state.pc.wrapping_sub(self.program.pc_start)) / 4) as usize;
+        let Some(&word) = self.program.instructions.get(index) else {
+            return self.trap(TrapCause::InstructionAddressMisaligned, self.state.pc);
+        };
+
+        match decode(word) {
+            Some(instr) => self.execute(instr)?,
+            None => return self.trap(TrapCause::IllegalInstruction, word),
+        }
+
+        self.state.clk = self.state.clk.wrapping_add(4);
+        self.state.global_clk += 1;
+        Ok(())
+    }
+
+    fn execute(&mut self, instr: Instruction) -> Result<(), TrapCause> {
+        match instr {
+            Instruction::Op { op, rd, rs1, rs2 } => {
+                let a = self.state.get_register(rs1).value;
+                let b = self.state.get_register(rs2).value;
+                let value = match op {
+                    AluOp::Add => a.wrapping_add(b),
+                    AluOp::Sub => a.wrapping_sub(b),
+                    AluOp::And => a & b,
+                    AluOp::Or => a | b,
+                    AluOp::Xor => a ^ b,
+                };
+                self.state.set_register(rd, MemoryRecord { value, ..Default::default() });
+                self.state.pc = self.state.pc.wrapping_add(4);
+            }
+            Instruction::OpImm { op, rd, rs1, imm } => {
+                let a = self.state.get_register(rs1).value as i32;
+                let value = match op {
+                    AluImmOp::Addi => a.wrapping_add(imm) as u32,
+                    AluImmOp::Slti => u32::from(a < imm),
+                };
+                self.state.set_register(rd, MemoryRecord { value, ..Default::default() });
+                self.state.pc = self.state.pc.wrapping_add(4);
+            }
+            Instruction::Load { rd, rs1, imm } => {
+                let addr = (self.state.get_register(rs1).value as i32).wrapping_add(imm) as u32;
+                if addr % 4 != 0 {
+                    return self.trap(TrapCause::LoadAddressMisaligned, addr);
+                }
+                let value = self.state.read_memory(addr).value;
+                self.state.set_register(rd, MemoryRecord { value, ..Default::default() });
+                self.state.pc = self.state.pc.wrapping_add(4);
+            }
+            Instruction::Store { rs1, rs2, imm } => {
+                let addr = (self.state.get_register(rs1).value as i32).wrapping_add(imm) as u32;
+                if addr % 4 != 0 {
+                    return self.trap(TrapCause::StoreAddressMisaligned, addr);
+                }
+                let value = self.state.get_register(rs2).value;
+                self.state.write_memory(addr, MemoryRecord { value, ..Default::default() });
+                self.state.pc = self.state.pc.wrapping_add(4);
+            }
+            Instruction::Branch { op, rs1, rs2, imm } => {
+                let taken = match op {
+                    BranchOp::Beq => {
+                        self.state.get_register(rs1).value == self.state.get_register(rs2).value
+                    }
+                    BranchOp::Bne => {
+                        self.state.get_register(rs1).value != self.state.get_register(rs2).value
+                    }
+                };
+                self.state.pc = if taken {
+                    (self.state.pc as i32).wrapping_add(imm) as u32
+                } else {
+                    self.state.pc.wrapping_add(4)
+                };
+            }
+            Instruction::Jal { rd, imm } => {
+                let link = self.state.pc.wrapping_add(4);
+                self.state.set_register(rd, MemoryRecord { value: link, ..Default::default() });
+                self.state.pc = (self.state.pc as i32).wrapping_add(imm) as u32;
+            }
+            Instruction::Ecall => {
+                return self.trap(TrapCause::EnvironmentCallFromMMode, 0);
+            }
+            Instruction::Csr(csr_instr) => self.execute_csr(csr_instr)?,
+            Instruction::Fp(fp_instr) => match self.state.execute_fp(fp_instr) {
+                Ok(()) => self.state.pc = self.state.pc.wrapping_add(4),
+                Err(cause) => return self.trap(cause, 0),
+            },
+        }
+        Ok(())
+    }
+
+    fn execute_csr(&mut self, instr: CsrInstruction) -> Result<(), TrapCause> {
+        let Some(old) = self.state.csrs.read(instr.csr) else {
+            return self.trap(TrapCause::IllegalInstruction, instr.csr);
+        };
+        let operand = match instr.operand {
+            CsrOperand::Register(reg) => self.state.get_register(reg).value,
+            CsrOperand::Immediate(imm) => imm,
+        };
+        let new = match instr.op {
+            CsrOp::ReadWrite => operand,
+            CsrOp::ReadSet => old | operand,
+            CsrOp::ReadClear => old & !operand,
+        };
+        if !self.state.csrs.write(instr.csr, new) {
+            return self.trap(TrapCause::IllegalInstruction, instr.csr);
+        }
+        self.state.set_register(instr.rd, MemoryRecord { value: old, ..Default::default() });
+        self.state.pc = self.state.pc.wrapping_add(4);
+        Ok(())
+    }
+
+    /// Take `cause` and jump to the installed handler, or bail out with
+    /// `Err(cause)` without touching `pc` if no handler is installed
+    /// (`mtvec == 0`) — see [`Executor::run`]'s docs.
+    fn trap(&mut self, cause: TrapCause, tval: u32) -> Result<(), TrapCause> {
+        if self.state.csrs.mtvec == 0 {
+            return Err(cause);
+        }
+        self.state.take_trap(cause, tval);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn i_type(opcode: u32, funct3: u32, rd: u32, rs1: u32, imm12: u32) -> u32 {
+        ((imm12 & 0xFFF) << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+    }
+
+    #[test]
+    fn run_executes_addi_and_stops_at_the_end_of_the_program() {
+        // addi x1, x0, 5
+        let word = i_type(crate::OPCODE_OP_IMM, 0b000, 1, 0, 5);
+        let mut executor = Executor::new(Program::new(vec![word], 0, 0));
+
+        executor.run(100).unwrap();
+
+        assert_eq!(executor.state.get_register(1).value, 5);
+        assert_eq!(executor.state.pc, 4);
+    }
+
+    #[test]
+    fn run_stops_after_max_cycles_even_on_a_tight_loop() {
+        // jal x0, 0 (unconditional self-jump; rd = x0, imm = 0)
+        let mut executor = Executor::new(Program::new(vec![crate::OPCODE_JAL], 0, 0));
+
+        executor.run(10).unwrap();
+
+        assert_eq!(executor.state.global_clk, 10);
+    }
+
+    #[test]
+    fn ecall_with_no_handler_installed_returns_the_trap_cause_without_looping() {
+        let mut executor = Executor::new(Program::new(vec![crate::OPCODE_SYSTEM], 0, 0));
+
+        let result = executor.run(100);
+
+        assert_eq!(result, Err(TrapCause::EnvironmentCallFromMMode));
+    }
+
+    #[test]
+    fn ecall_with_a_handler_installed_jumps_to_mtvec() {
+        let mut executor = Executor::new(Program::new(vec![crate::OPCODE_SYSTEM], 0, 0));
+        executor.state.csrs.mtvec = 0x8000;
+
+        let result = executor.run(1);
+
+        assert!(result.is_ok());
+        assert_eq!(executor.state.pc, 0x8000);
+        assert_eq!(executor.state.csrs.mcause, TrapCause::EnvironmentCallFromMMode.mcause());
+    }
+
+    #[test]
+    fn run_executes_csrrs_against_a_real_csr_and_retires_normally() {
+        // csrrs x1, mstatus, x0 (reads mstatus into x1, writes nothing back
+        // since rs1 = x0)
+        let word = i_type(crate::OPCODE_SYSTEM, 0b010, 1, 0, crate::CSR_MSTATUS);
+        let mut executor = Executor::new(Program::new(vec![word], 0, 0));
+        executor.state.csrs.mstatus = 0xABCD;
+
+        executor.run(1).unwrap();
+
+        assert_eq!(executor.state.get_register(1).value, 0xABCD);
+        assert_eq!(executor.state.csrs.mstatus, 0xABCD, "rs1 = x0 must not modify mstatus");
+        assert_eq!(executor.state.pc, 4);
+    }
+
+    #[test]
+    fn csrrw_with_an_unimplemented_csr_traps_when_a_handler_is_installed() {
+        // csrrw x1, <unimplemented CSR 0x7FF>, x0
+        let word = i_type(crate::OPCODE_SYSTEM, 0b001, 1, 0, 0x7FF);
+        let mut executor = Executor::new(Program::new(vec![word], 0, 0));
+        executor.state.csrs.mtvec = 0x8000;
+
+        executor.run(1).unwrap();
+
+        assert_eq!(executor.state.pc, 0x8000);
+        assert_eq!(executor.state.csrs.mcause, TrapCause::IllegalInstruction.mcause());
+    }
+
+    #[test]
+    fn illegal_instruction_traps_when_a_handler_is_installed() {
+        let mut executor = Executor::new(Program::new(vec![0b1111111], 0, 0));
+        executor.state.csrs.mtvec = 0x8000;
+
+        executor.run(1).unwrap();
+
+        assert_eq!(executor.state.pc, 0x8000);
+        assert_eq!(executor.state.csrs.mcause, TrapCause::IllegalInstruction.mcause());
+    }
+}
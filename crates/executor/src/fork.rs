@@ -0,0 +1,34 @@
+//! `Executor`-facing checkpoint/restore API.
+//!
+//! The actual diff logging lives on [`crate::ExecutionState`] (see
+//! `begin_fork`/`end_fork_restore`/`end_fork_commit`), since that's where
+//! memory, registers, `pc`, and the clocks already live; this module just
+//! threads `executor_mode` through so a session can be checkpointed,
+//! advanced, and deterministically replayed without re-running from cycle
+//! zero — useful for debugging long executions like the one in the
+//! benchmark `main`.
+
+pub use crate::ForkHandle;
+use crate::Executor;
+
+impl Executor {
+    /// Begin recording memory mutations from this point on, returning a
+    /// handle that can later be passed to [`Executor::restore`] or
+    /// [`Executor::commit`].
+    pub fn fork(&mut self) -> ForkHandle {
+        self.state.begin_fork(self.executor_mode)
+    }
+
+    /// Roll execution back to `handle`'s fork point: memory, registers,
+    /// `pc`, `clk`, `global_clk`, and `executor_mode` are all restored to
+    /// what they were when [`Executor::fork`] was called.
+    pub fn restore(&mut self, handle: ForkHandle) {
+        self.executor_mode = self.state.end_fork_restore(handle);
+    }
+
+    /// Discard `handle`'s diff log, keeping all changes made since the fork
+    /// point.
+    pub fn commit(&mut self, handle: ForkHandle) {
+        self.state.end_fork_commit(handle);
+    }
+}
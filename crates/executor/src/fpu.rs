@@ -0,0 +1,1038 @@
+//! RV32F/RV32D software floating-point support: the 32 `f`-registers,
+//! NaN-boxing, and rounding-mode-aware conversion from the wider precision
+//! operations are computed in down to the width RISC-V actually stores.
+//!
+//! Each FP instruction carries a 3-bit `rm` field; `0b111` ("dynamic") means
+//! "use `fcsr.frm` instead", and every other encoding is a fixed mode. Since
+//! Rust's native `f32`/`f64` arithmetic only ever rounds to nearest-even,
+//! anything other than [`RoundingMode::RoundNearestEven`] has to be
+//! implemented by hand, which is why [`round_f64_to_f32`] exists.
+
+use crate::{
+    events::MemoryRecord, CsrFile, ExecutionState, TrapCause, FFLAG_DZ, FFLAG_NV, FFLAG_NX, FFLAG_OF,
+    FFLAG_UF,
+};
+
+/// The upper 32 bits of a properly NaN-boxed single-precision value.
+const NAN_BOX_UPPER: u64 = 0xFFFF_FFFF_0000_0000;
+
+/// Box `value` into the lower 32 bits of an `f`-register per the RISC-V
+/// NaN-boxing convention (upper 32 bits all set).
+#[must_use]
+pub fn box_f32(value: f32) -> u64 {
+    NAN_BOX_UPPER | u64::from(value.to_bits())
+}
+
+/// Unbox a single-precision value from an `f`-register. Per spec, a value
+/// that is not properly NaN-boxed (upper bits not all set) reads back as the
+/// canonical quiet NaN rather than as whatever garbage is in the low bits.
+#[must_use]
+pub fn unbox_f32(bits: u64) -> f32 {
+    if bits & NAN_BOX_UPPER == NAN_BOX_UPPER {
+        f32::from_bits(bits as u32)
+    } else {
+        f32::from_bits(CANONICAL_NAN_F32)
+    }
+}
+
+/// Box a double-precision value: the identity, since `f`-registers are
+/// already 64 bits wide.
+#[must_use]
+pub fn box_f64(value: f64) -> u64 {
+    value.to_bits()
+}
+
+/// Unbox a double-precision value from an `f`-register.
+#[must_use]
+pub fn unbox_f64(bits: u64) -> f64 {
+    f64::from_bits(bits)
+}
+
+/// The canonical single-precision quiet NaN (`0x7fc00000`).
+pub const CANONICAL_NAN_F32: u32 = 0x7FC0_0000;
+/// The canonical double-precision quiet NaN (`0x7ff8000000000000`).
+pub const CANONICAL_NAN_F64: u64 = 0x7FF8_0000_0000_0000;
+
+/// One of the five rounding modes RV32F/RV32D instructions can request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// `000` - round to nearest, ties to even.
+    RoundNearestEven,
+    /// `001` - round toward zero (truncate).
+    RoundTowardZero,
+    /// `010` - round toward −∞ (floor).
+    RoundDown,
+    /// `011` - round toward +∞ (ceiling).
+    RoundUp,
+    /// `100` - round to nearest, ties away from zero (to max magnitude).
+    RoundNearestMaxMagnitude,
+}
+
+impl RoundingMode {
+    /// Decode a 3-bit instruction `rm` field, resolving `0b111` ("dynamic")
+    /// against `fcsr.frm`.
+    ///
+    /// Returns `None` for a reserved encoding (`0b101`/`0b110`, or a
+    /// reserved value left in `frm` when `rm` is dynamic) — the caller
+    /// should turn that into an illegal-instruction trap.
+    #[must_use]
+    pub fn decode(rm: u32, csrs: &CsrFile) -> Option<Self> {
+        let effective = if rm == 0b111 { csrs.frm() } else { rm };
+        match effective {
+            0b000 => Some(RoundingMode::RoundNearestEven),
+            0b001 => Some(RoundingMode::RoundTowardZero),
+            0b010 => Some(RoundingMode::RoundDown),
+            0b011 => Some(RoundingMode::RoundUp),
+            0b100 => Some(RoundingMode::RoundNearestMaxMagnitude),
+            _ => None,
+        }
+    }
+}
+
+/// Round an exact (or wider-precision) `f64` intermediate result down to
+/// `f32` per `mode`, matching RISC-V's rounding semantics rather than Rust's
+/// native (always round-nearest-even) `as f32` cast.
+///
+/// Operating on a 53-bit `f64` significand and dropping to `f32`'s 24 bits
+/// loses fewer bits than most individual RV32F operations need, which is why
+/// callers (e.g. `fadd.s`/`fmul.s`) compute in `f64` and funnel the result
+/// through here rather than using `f32` arithmetic directly.
+///
+/// Returns the rounded value and whether rounding was inexact (for the `NX`
+/// accrued flag) together with whether it overflowed (for `OF`).
+#[must_use]
+pub fn round_f64_to_f32(value: f64, mode: RoundingMode) -> (f32, bool, bool) {
+    if value.is_nan() {
+        return (f32::from_bits(CANONICAL_NAN_F32), false, false);
+    }
+    if value == 0.0 {
+        return (if value.is_sign_negative() { -0.0 } else { 0.0 }, false, false);
+    }
+    if value.is_infinite() {
+        return (if value.is_sign_negative() { f32::NEG_INFINITY } else { f32::INFINITY }, false, false);
+    }
+
+    let negative = value.is_sign_negative();
+    let bits = value.to_bits();
+    let biased_exp = ((bits >> 52) & 0x7FF) as i32;
+    let frac = bits & 0x000F_FFFF_FFFF_FFFF;
+
+    // A 53-bit significand (implicit leading 1 included) and the exponent of
+    // its leading bit, i.e. `value == +/-significand * 2^(unbiased_exp - 52)`.
+    let (significand, unbiased_exp): (u64, i32) =
+        if biased_exp == 0 { (frac, -1022) } else { ((1u64 << 52) | frac, biased_exp - 1023) };
+
+    // f32 can only represent 24 significant bits, so 53 - 24 = 29 bits get
+    // dropped in the normal-exponent-range case; a result whose exponent is
+    // below f32's normal range drops additional bits, "denormalizing" it into
+    // a subnormal (or zero) f32 -- the same trick a hardware FPU uses.
+    let subnormal_shift = (-126 - unbiased_exp).max(0);
+    let drop_bits = 29 + subnormal_shift;
+
+    if drop_bits >= 64 {
+        // Rounds to zero however `mode` is applied, except that RDN/RUP must
+        // still produce the smallest nonzero magnitude in the direction that
+        // doesn't round toward zero.
+        let round_away = matches!(
+            (mode, negative),
+            (RoundingMode::RoundDown, true) | (RoundingMode::RoundUp, false)
+        );
+        let rounded = if round_away { f32::from_bits(1) } else { 0.0 };
+        return (if negative { -rounded } else { rounded }, true, false);
+    }
+
+    let guard_bit = (significand >> (drop_bits - 1)) & 1;
+    let sticky = drop_bits > 1 && (significand & ((1u64 << (drop_bits - 1)) - 1)) != 0;
+    let mut kept = significand >> drop_bits;
+    let inexact = guard_bit == 1 || sticky;
+
+    let round_up = match mode {
+        RoundingMode::RoundNearestEven => guard_bit == 1 && (sticky || kept & 1 == 1),
+        RoundingMode::RoundNearestMaxMagnitude => guard_bit == 1,
+        RoundingMode::RoundTowardZero => false,
+        RoundingMode::RoundDown => negative && inexact,
+        RoundingMode::RoundUp => !negative && inexact,
+    };
+
+    let mut target_exp = unbiased_exp;
+    if round_up {
+        kept += 1;
+        if kept >= 1 << 24 {
+            kept >>= 1;
+            target_exp += 1;
+        }
+    }
+
+    if target_exp > 127 {
+        // Modes that round toward zero for this sign stop at the largest
+        // finite magnitude instead of overflowing to infinity.
+        let to_max_finite = match mode {
+            RoundingMode::RoundTowardZero => true,
+            RoundingMode::RoundDown => !negative,
+            RoundingMode::RoundUp => negative,
+            RoundingMode::RoundNearestEven | RoundingMode::RoundNearestMaxMagnitude => false,
+        };
+        let rounded = if to_max_finite { f32::MAX } else { f32::INFINITY };
+        return (if negative { -rounded } else { rounded }, true, true);
+    }
+
+    let bits = if target_exp >= -126 {
+        let exp_field = (target_exp + 127) as u32;
+        let frac_field = (kept as u32) & 0x007F_FFFF;
+        (exp_field << 23) | frac_field
+    } else {
+        // Subnormal: no implicit bit, `kept` already holds the full mantissa.
+        kept as u32
+    };
+
+    let rounded = f32::from_bits(bits);
+    (if negative { -rounded } else { rounded }, inexact, false)
+}
+
+impl ExecutionState {
+    /// Read the single-precision value in `f`-register `reg` (unboxing it).
+    #[must_use]
+    pub fn get_f32(&self, reg: usize) -> f32 {
+        unbox_f32(self.f_registers[reg])
+    }
+
+    /// Read the double-precision value in `f`-register `reg`.
+    #[must_use]
+    pub fn get_f64(&self, reg: usize) -> f64 {
+        unbox_f64(self.f_registers[reg])
+    }
+
+    /// Write a single-precision result into `f`-register `reg`, NaN-boxing
+    /// it. Unlike the integer registers, `f0` is a real, writable register.
+    pub fn set_f32(&mut self, reg: usize, value: f32) {
+        self.f_registers[reg] = box_f32(value);
+    }
+
+    /// Write a double-precision result into `f`-register `reg`.
+    pub fn set_f64(&mut self, reg: usize, value: f64) {
+        self.f_registers[reg] = box_f64(value);
+    }
+}
+
+/// Whether `value` is a signaling NaN, i.e. a NaN whose most significant
+/// mantissa bit ("the quiet bit") is clear. Operations that consume a
+/// signaling NaN must raise the invalid-operation (`NV`) flag.
+#[must_use]
+pub fn is_signaling_nan_f32(value: f32) -> bool {
+    let bits = value.to_bits();
+    value.is_nan() && bits & (1 << 22) == 0
+}
+
+/// Apply the `NV` flag if `a` or `b` is a signaling NaN, returning whether
+/// either operand was any kind of NaN (so the caller knows to short-circuit
+/// to the canonical NaN result).
+pub fn check_nan_inputs_f32(csrs: &mut CsrFile, a: f32, b: f32) -> bool {
+    if is_signaling_nan_f32(a) || is_signaling_nan_f32(b) {
+        csrs.accrue_fflags(FFLAG_NV);
+    }
+    a.is_nan() || b.is_nan()
+}
+
+/// The single-precision arithmetic/compare/convert ops reachable through
+/// `OP-FP` (`0b1010011`).
+///
+/// Scoped to `.s`: `.d` (double-precision) arithmetic is left as a
+/// follow-on — `get_f64`/`set_f64` above already exist for it, but nothing
+/// decodes or executes it yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FpOp {
+    /// `fadd.s`
+    Add,
+    /// `fsub.s`
+    Sub,
+    /// `fmul.s`
+    Mul,
+    /// `fdiv.s`
+    Div,
+    /// `fsqrt.s`
+    Sqrt,
+    /// `feq.s`
+    Eq,
+    /// `flt.s`
+    Lt,
+    /// `fle.s`
+    Le,
+    /// `fclass.s`
+    Class,
+    /// `fcvt.w.s`
+    CvtWS,
+    /// `fcvt.wu.s`
+    CvtWuS,
+    /// `fcvt.s.w`
+    CvtSW,
+    /// `fcvt.s.wu`
+    CvtSWu,
+}
+
+/// The four fused multiply-add flavors (`rd = ±(rs1 * rs2) ± rs3`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FmaOp {
+    /// `fmadd.s`: `(rs1 * rs2) + rs3`
+    Madd,
+    /// `fmsub.s`: `(rs1 * rs2) - rs3`
+    Msub,
+    /// `fnmsub.s`: `-(rs1 * rs2) + rs3`
+    Nmsub,
+    /// `fnmadd.s`: `-(rs1 * rs2) - rs3`
+    Nmadd,
+}
+
+/// A decoded `LOAD-FP`/`STORE-FP`/`OP-FP`/FMA instruction, scoped to the
+/// single-precision (`.s`) subset covered by [`FpOp`]/[`FmaOp`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FpInstruction {
+    /// `flw rd, imm(rs1)`
+    Load {
+        /// Destination `f`-register.
+        rd: usize,
+        /// Base address register.
+        rs1: usize,
+        /// Sign-extended 12-bit byte offset.
+        imm: i32,
+    },
+    /// `fsw rs2, imm(rs1)`
+    Store {
+        /// Base address register.
+        rs1: usize,
+        /// Source `f`-register.
+        rs2: usize,
+        /// Sign-extended 12-bit byte offset.
+        imm: i32,
+    },
+    /// An `OP-FP` arithmetic/compare/convert instruction.
+    Op {
+        /// Which operation.
+        op: FpOp,
+        /// Destination register: an `f`-register for arithmetic/convert-to-float,
+        /// an integer register for compares/`fclass.s`/convert-to-int.
+        rd: usize,
+        /// First source `f`-register (or the lone operand register for
+        /// `fcvt.*`/`fclass.s`).
+        rs1: usize,
+        /// Second source `f`-register; unused (and decoded as `0`) for
+        /// unary ops.
+        rs2: usize,
+        /// Raw `rm` field; only meaningful (and passed through
+        /// [`RoundingMode::decode`]) for ops that actually round.
+        rm: u32,
+    },
+    /// An `FMADD`/`FMSUB`/`FNMSUB`/`FNMADD` instruction.
+    Fma {
+        /// Which flavor.
+        op: FmaOp,
+        /// Destination `f`-register.
+        rd: usize,
+        /// First multiplicand.
+        rs1: usize,
+        /// Second multiplicand.
+        rs2: usize,
+        /// Addend/subtrahend.
+        rs3: usize,
+        /// Raw `rm` field.
+        rm: u32,
+    },
+}
+
+const OPCODE_LOAD_FP: u32 = 0b0000111;
+const OPCODE_STORE_FP: u32 = 0b0100111;
+const OPCODE_OP_FP: u32 = 0b1010011;
+const OPCODE_FMADD: u32 = 0b1000011;
+const OPCODE_FMSUB: u32 = 0b1000111;
+const OPCODE_FNMSUB: u32 = 0b1001011;
+const OPCODE_FNMADD: u32 = 0b1001111;
+
+/// Decode `word` as a `LOAD-FP`/`STORE-FP`/`OP-FP`/FMA instruction.
+///
+/// Returns `None` for any other opcode, for a `.d` (double-precision) `fmt`
+/// field, or for an `OP-FP` `funct5`/`rs2` combination this decoder doesn't
+/// recognize (e.g. `fsgnj.s`/`fmin.s`/`fmax.s`/`fmv.*`, which aren't wired up
+/// yet) — the caller should treat that the same as any other undecodable
+/// instruction word.
+#[must_use]
+pub fn decode_fp(word: u32) -> Option<FpInstruction> {
+    let opcode = word & 0x7F;
+    let rd = ((word >> 7) & 0x1F) as usize;
+    let funct3 = (word >> 12) & 0x7;
+    let rs1 = ((word >> 15) & 0x1F) as usize;
+    let rs2 = ((word >> 20) & 0x1F) as usize;
+    // Bits [31:25]: `funct7` for `OP-FP`, or `rs3 << 2 | fmt` for the R4-type
+    // FMA opcodes — either way, its low 2 bits are the `fmt` field.
+    let top7 = (word >> 25) & 0x7F;
+
+    match opcode {
+        OPCODE_LOAD_FP if funct3 == 0b010 => {
+            let imm = sign_extend(word >> 20, 12);
+            Some(FpInstruction::Load { rd, rs1, imm })
+        }
+        OPCODE_STORE_FP if funct3 == 0b010 => {
+            let imm_lo = (word >> 7) & 0x1F;
+            let imm_hi = top7;
+            let imm = sign_extend((imm_hi << 5) | imm_lo, 12);
+            Some(FpInstruction::Store { rs1, rs2, imm })
+        }
+        OPCODE_OP_FP => {
+            let funct5 = top7 >> 2;
+            let fmt = top7 & 0b11;
+            if fmt != 0b00 {
+                return None;
+            }
+            let (op, rm) = match funct5 {
+                0b00000 => (FpOp::Add, funct3),
+                0b00001 => (FpOp::Sub, funct3),
+                0b00010 => (FpOp::Mul, funct3),
+                0b00011 => (FpOp::Div, funct3),
+                0b01011 if rs2 == 0b00000 => (FpOp::Sqrt, funct3),
+                0b10100 => match funct3 {
+                    0b010 => (FpOp::Eq, 0),
+                    0b001 => (FpOp::Lt, 0),
+                    0b000 => (FpOp::Le, 0),
+                    _ => return None,
+                },
+                0b11100 if rs2 == 0b00000 && funct3 == 0b001 => (FpOp::Class, 0),
+                0b11000 => match rs2 {
+                    0b00000 => (FpOp::CvtWS, funct3),
+                    0b00001 => (FpOp::CvtWuS, funct3),
+                    _ => return None,
+                },
+                0b11010 => match rs2 {
+                    0b00000 => (FpOp::CvtSW, funct3),
+                    0b00001 => (FpOp::CvtSWu, funct3),
+                    _ => return None,
+                },
+                _ => return None,
+            };
+            Some(FpInstruction::Op { op, rd, rs1, rs2, rm })
+        }
+        OPCODE_FMADD | OPCODE_FMSUB | OPCODE_FNMSUB | OPCODE_FNMADD => {
+            let fmt = top7 & 0b11;
+            if fmt != 0b00 {
+                return None;
+            }
+            let rs3 = (top7 >> 2) as usize;
+            let op = match opcode {
+                OPCODE_FMADD => FmaOp::Madd,
+                OPCODE_FMSUB => FmaOp::Msub,
+                OPCODE_FNMSUB => FmaOp::Nmsub,
+                OPCODE_FNMADD => FmaOp::Nmadd,
+                _ => unreachable!(),
+            };
+            Some(FpInstruction::Fma { op, rd, rs1, rs2, rs3, rm: funct3 })
+        }
+        _ => None,
+    }
+}
+
+/// Sign-extend the low `bits` bits of `value` to a full `i32`.
+fn sign_extend(value: u32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    ((value << shift) as i32) >> shift
+}
+
+/// The RISC-V `fclass.s` classification bitmask for `value` (exactly one bit
+/// set, from "negative infinity" at bit 0 to "quiet NaN" at bit 9).
+#[must_use]
+fn fclass_f32(value: f32) -> u32 {
+    let negative = value.is_sign_negative();
+    if value.is_nan() {
+        return if is_signaling_nan_f32(value) { 1 << 8 } else { 1 << 9 };
+    }
+    if value.is_infinite() {
+        return if negative { 1 << 0 } else { 1 << 7 };
+    }
+    if value == 0.0 {
+        return if negative { 1 << 3 } else { 1 << 4 };
+    }
+    let is_subnormal = value.to_bits() & 0x7F80_0000 == 0;
+    match (negative, is_subnormal) {
+        (true, true) => 1 << 2,
+        (true, false) => 1 << 1,
+        (false, true) => 1 << 5,
+        (false, false) => 1 << 6,
+    }
+}
+
+/// Round `value` to the nearest representable integer per `mode`, as an
+/// `f64` so the caller can check it against the target integer type's range
+/// before converting (RISC-V `fcvt.*` saturates out-of-range inputs rather
+/// than wrapping).
+fn round_f32_to_nearest_integer(value: f32, mode: RoundingMode) -> f64 {
+    let value = f64::from(value);
+    let floor = value.floor();
+    match mode {
+        RoundingMode::RoundTowardZero => value.trunc(),
+        RoundingMode::RoundDown => floor,
+        RoundingMode::RoundUp => value.ceil(),
+        RoundingMode::RoundNearestEven | RoundingMode::RoundNearestMaxMagnitude => {
+            let diff = value - floor;
+            if diff < 0.5 {
+                floor
+            } else if diff > 0.5 {
+                floor + 1.0
+            } else if mode == RoundingMode::RoundNearestMaxMagnitude {
+                if value >= 0.0 { floor + 1.0 } else { floor }
+            } else if (floor as i64) % 2 == 0 {
+                floor
+            } else {
+                floor + 1.0
+            }
+        }
+    }
+}
+
+impl ExecutionState {
+    /// Execute a decoded `LOAD-FP`/`STORE-FP`/`OP-FP`/FMA instruction.
+    pub fn execute_fp(&mut self, instr: FpInstruction) -> Result<(), TrapCause> {
+        match instr {
+            FpInstruction::Load { rd, rs1, imm } => {
+                let addr = (self.get_register(rs1).value as i32).wrapping_add(imm) as u32;
+                let bits = self.read_memory(addr).value;
+                self.set_f32(rd, f32::from_bits(bits));
+                Ok(())
+            }
+            FpInstruction::Store { rs1, rs2, imm } => {
+                let addr = (self.get_register(rs1).value as i32).wrapping_add(imm) as u32;
+                let bits = self.get_f32(rs2).to_bits();
+                self.write_memory(addr, MemoryRecord { value: bits, ..Default::default() });
+                Ok(())
+            }
+            FpInstruction::Op { op, rd, rs1, rs2, rm } => self.execute_fp_op(op, rd, rs1, rs2, rm),
+            FpInstruction::Fma { op, rd, rs1, rs2, rs3, rm } => {
+                self.execute_fp_fma(op, rd, rs1, rs2, rs3, rm)
+            }
+        }
+    }
+
+    /// Accrue `OF`/`NX` (and an underflow approximation for `UF`: an inexact
+    /// result whose magnitude landed below the smallest normal `f32`) for a
+    /// [`round_f64_to_f32`] result.
+    fn accrue_rounding_flags(&mut self, result: f32, inexact: bool, overflow: bool) {
+        if overflow {
+            self.csrs.accrue_fflags(FFLAG_OF);
+        }
+        if inexact {
+            self.csrs.accrue_fflags(FFLAG_NX);
+            if result.abs() < f32::MIN_POSITIVE {
+                self.csrs.accrue_fflags(FFLAG_UF);
+            }
+        }
+    }
+
+    // `feq.s` is specified as an exact IEEE-754 equality comparison (so
+    // `+0.0 == -0.0`), not an approximate one, so `a == b` below is intentional.
+    #[allow(clippy::float_cmp)]
+    fn execute_fp_op(
+        &mut self,
+        op: FpOp,
+        rd: usize,
+        rs1: usize,
+        rs2: usize,
+        rm: u32,
+    ) -> Result<(), TrapCause> {
+        match op {
+            FpOp::Add | FpOp::Sub | FpOp::Mul | FpOp::Div => {
+                let mode = RoundingMode::decode(rm, &self.csrs).ok_or(TrapCause::IllegalInstruction)?;
+                let a = self.get_f32(rs1);
+                let b = self.get_f32(rs2);
+                if check_nan_inputs_f32(&mut self.csrs, a, b) {
+                    self.set_f32(rd, f32::from_bits(CANONICAL_NAN_F32));
+                    return Ok(());
+                }
+                if op == FpOp::Div && b == 0.0 && a != 0.0 {
+                    self.csrs.accrue_fflags(FFLAG_DZ);
+                }
+                let (a, b) = (f64::from(a), f64::from(b));
+                let exact = match op {
+                    FpOp::Add => a + b,
+                    FpOp::Sub => a - b,
+                    FpOp::Mul => a * b,
+                    FpOp::Div => a / b,
+                    _ => unreachable!(),
+                };
+                // Neither input was NaN (checked above), so a NaN result here
+                // can only come from an invalid-operation pattern the IEEE-754
+                // spec defines in terms of the result rather than the inputs:
+                // 0*inf, inf-inf/-inf+inf, or 0/0, inf/inf.
+                if exact.is_nan() {
+                    self.csrs.accrue_fflags(FFLAG_NV);
+                    self.set_f32(rd, f32::from_bits(CANONICAL_NAN_F32));
+                    return Ok(());
+                }
+                let (result, inexact, overflow) = round_f64_to_f32(exact, mode);
+                self.accrue_rounding_flags(result, inexact, overflow);
+                self.set_f32(rd, result);
+                Ok(())
+            }
+            FpOp::Sqrt => {
+                let mode = RoundingMode::decode(rm, &self.csrs).ok_or(TrapCause::IllegalInstruction)?;
+                let a = self.get_f32(rs1);
+                if is_signaling_nan_f32(a) {
+                    self.csrs.accrue_fflags(FFLAG_NV);
+                }
+                if a.is_nan() || a < 0.0 {
+                    if !a.is_nan() {
+                        self.csrs.accrue_fflags(FFLAG_NV);
+                    }
+                    self.set_f32(rd, f32::from_bits(CANONICAL_NAN_F32));
+                    return Ok(());
+                }
+                let (result, inexact, overflow) = round_f64_to_f32(f64::from(a).sqrt(), mode);
+                self.accrue_rounding_flags(result, inexact, overflow);
+                self.set_f32(rd, result);
+                Ok(())
+            }
+            FpOp::Eq | FpOp::Lt | FpOp::Le => {
+                let a = self.get_f32(rs1);
+                let b = self.get_f32(rs2);
+                let any_nan = a.is_nan() || b.is_nan();
+                // FEQ.S only signals NV on signaling NaNs; FLT.S/FLE.S signal
+                // on any NaN operand, signaling or quiet.
+                if is_signaling_nan_f32(a) || is_signaling_nan_f32(b) || (op != FpOp::Eq && any_nan) {
+                    self.csrs.accrue_fflags(FFLAG_NV);
+                }
+                let value = if any_nan {
+                    0
+                } else {
+                    match op {
+                        FpOp::Eq => u32::from(a == b),
+                        FpOp::Lt => u32::from(a < b),
+                        FpOp::Le => u32::from(a <= b),
+                        _ => unreachable!(),
+                    }
+                };
+                self.set_register(rd, MemoryRecord { value, ..Default::default() });
+                Ok(())
+            }
+            FpOp::Class => {
+                let value = fclass_f32(self.get_f32(rs1));
+                self.set_register(rd, MemoryRecord { value, ..Default::default() });
+                Ok(())
+            }
+            FpOp::CvtWS | FpOp::CvtWuS => {
+                let mode = RoundingMode::decode(rm, &self.csrs).ok_or(TrapCause::IllegalInstruction)?;
+                let unsigned = op == FpOp::CvtWuS;
+                let value = self.convert_f32_to_int(self.get_f32(rs1), unsigned, mode);
+                self.set_register(rd, MemoryRecord { value, ..Default::default() });
+                Ok(())
+            }
+            FpOp::CvtSW | FpOp::CvtSWu => {
+                let mode = RoundingMode::decode(rm, &self.csrs).ok_or(TrapCause::IllegalInstruction)?;
+                let bits = self.get_register(rs1).value;
+                let exact = if op == FpOp::CvtSWu { f64::from(bits) } else { f64::from(bits as i32) };
+                let (result, inexact, overflow) = round_f64_to_f32(exact, mode);
+                self.accrue_rounding_flags(result, inexact, overflow);
+                self.set_f32(rd, result);
+                Ok(())
+            }
+        }
+    }
+
+    /// Convert a single-precision value to a 32-bit integer per `mode`,
+    /// saturating (and raising `NV`) on a NaN input or an out-of-range
+    /// result rather than wrapping.
+    fn convert_f32_to_int(&mut self, value: f32, unsigned: bool, mode: RoundingMode) -> u32 {
+        if value.is_nan() {
+            self.csrs.accrue_fflags(FFLAG_NV);
+            return if unsigned { u32::MAX } else { i32::MAX as u32 };
+        }
+
+        let rounded = round_f32_to_nearest_integer(value, mode);
+        if unsigned {
+            if rounded < 0.0 {
+                self.csrs.accrue_fflags(FFLAG_NV);
+                0
+            } else if rounded > f64::from(u32::MAX) {
+                self.csrs.accrue_fflags(FFLAG_NV);
+                u32::MAX
+            } else {
+                rounded as u32
+            }
+        } else if rounded < f64::from(i32::MIN) {
+            self.csrs.accrue_fflags(FFLAG_NV);
+            i32::MIN as u32
+        } else if rounded > f64::from(i32::MAX) {
+            self.csrs.accrue_fflags(FFLAG_NV);
+            i32::MAX as u32
+        } else {
+            (rounded as i32) as u32
+        }
+    }
+
+    fn execute_fp_fma(
+        &mut self,
+        op: FmaOp,
+        rd: usize,
+        rs1: usize,
+        rs2: usize,
+        rs3: usize,
+        rm: u32,
+    ) -> Result<(), TrapCause> {
+        let mode = RoundingMode::decode(rm, &self.csrs).ok_or(TrapCause::IllegalInstruction)?;
+        let a = self.get_f32(rs1);
+        let b = self.get_f32(rs2);
+        let c = self.get_f32(rs3);
+        if is_signaling_nan_f32(a) || is_signaling_nan_f32(b) || is_signaling_nan_f32(c) {
+            self.csrs.accrue_fflags(FFLAG_NV);
+        }
+        if a.is_nan() || b.is_nan() || c.is_nan() {
+            self.set_f32(rd, f32::from_bits(CANONICAL_NAN_F32));
+            return Ok(());
+        }
+
+        let product = f64::from(a) * f64::from(b);
+        let c = f64::from(c);
+        let exact = match op {
+            FmaOp::Madd => product + c,
+            FmaOp::Msub => product - c,
+            FmaOp::Nmsub => -product + c,
+            FmaOp::Nmadd => -product - c,
+        };
+        // As in `execute_fp_op`: no input was NaN, so a NaN `exact` here means
+        // the product-addition hit an invalid-operation pattern, e.g.
+        // `inf * 0.0 + c` or the addition itself cancelling two infinities.
+        if exact.is_nan() {
+            self.csrs.accrue_fflags(FFLAG_NV);
+            self.set_f32(rd, f32::from_bits(CANONICAL_NAN_F32));
+            return Ok(());
+        }
+        let (result, inexact, overflow) = round_f64_to_f32(exact, mode);
+        self.accrue_rounding_flags(result, inexact, overflow);
+        self.set_f32(rd, result);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp, clippy::manual_midpoint, clippy::manual_range_contains)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nan_boxing_round_trips_finite_values() {
+        for value in [0.0f32, -0.0, 1.0, -1.0, 1234.5, f32::MIN_POSITIVE, f32::MAX] {
+            assert_eq!(unbox_f32(box_f32(value)).to_bits(), value.to_bits());
+        }
+    }
+
+    #[test]
+    fn improperly_boxed_value_reads_as_canonical_nan() {
+        // Upper bits not all 1s => not a valid NaN box.
+        assert_eq!(unbox_f32(0x0000_0000_3F80_0000).to_bits(), CANONICAL_NAN_F32);
+    }
+
+    #[test]
+    fn rounding_mode_decode_resolves_dynamic_and_rejects_reserved() {
+        let mut csrs = CsrFile::default();
+        csrs.fcsr = 0b010 << 5; // frm = RDN
+
+        assert_eq!(RoundingMode::decode(0b111, &csrs), Some(RoundingMode::RoundDown));
+        assert_eq!(RoundingMode::decode(0b000, &csrs), Some(RoundingMode::RoundNearestEven));
+        assert_eq!(RoundingMode::decode(0b101, &csrs), None);
+
+        csrs.fcsr = 0b101 << 5; // reserved frm
+        assert_eq!(RoundingMode::decode(0b111, &csrs), None);
+    }
+
+    #[test]
+    fn round_f64_to_f32_is_exact_round_trip_when_representable() {
+        let (value, inexact, overflow) = round_f64_to_f32(1.5_f64, RoundingMode::RoundNearestEven);
+        assert_eq!(value, 1.5_f32);
+        assert!(!inexact);
+        assert!(!overflow);
+    }
+
+    #[test]
+    fn round_f64_to_f32_ties_to_even_vs_ties_to_max_magnitude() {
+        // The f64 value exactly halfway between two adjacent f32s just above 1.0:
+        // 1.0 + 2^-24 lands precisely on the rounding boundary.
+        let low = 1.0_f32;
+        let high = f32::from_bits(low.to_bits() + 1);
+        let midpoint = (f64::from(low) + f64::from(high)) / 2.0;
+
+        let (nearest_even, inexact_even, _) = round_f64_to_f32(midpoint, RoundingMode::RoundNearestEven);
+        let (nearest_max, inexact_max, _) =
+            round_f64_to_f32(midpoint, RoundingMode::RoundNearestMaxMagnitude);
+
+        // `low`'s mantissa is even (ties-to-even keeps it); ties-to-max-magnitude
+        // always rounds away from zero, landing on `high`.
+        assert_eq!(nearest_even, low);
+        assert_eq!(nearest_max, high);
+        assert!(inexact_even && inexact_max);
+    }
+
+    #[test]
+    fn round_f64_to_f32_toward_zero_truncates_both_signs() {
+        let (pos, pos_inexact, _) = round_f64_to_f32(1.9999999_f64, RoundingMode::RoundTowardZero);
+        let (neg, neg_inexact, _) = round_f64_to_f32(-1.9999999_f64, RoundingMode::RoundTowardZero);
+
+        assert!(pos <= 1.9999999_f32 && pos >= 1.0);
+        assert!(neg >= -1.9999999_f32 && neg <= -1.0);
+        assert!(pos_inexact && neg_inexact);
+    }
+
+    #[test]
+    fn round_f64_to_f32_down_and_up_are_direction_sensitive() {
+        let value = 1.0000001_f64;
+
+        let (down, _, _) = round_f64_to_f32(value, RoundingMode::RoundDown);
+        let (up, _, _) = round_f64_to_f32(value, RoundingMode::RoundUp);
+        assert!(f64::from(down) <= value);
+        assert!(f64::from(up) >= value);
+
+        let (down_neg, _, _) = round_f64_to_f32(-value, RoundingMode::RoundDown);
+        let (up_neg, _, _) = round_f64_to_f32(-value, RoundingMode::RoundUp);
+        assert!(f64::from(down_neg) <= -value);
+        assert!(f64::from(up_neg) >= -value);
+    }
+
+    #[test]
+    fn round_f64_to_f32_overflow_produces_infinity() {
+        let (value, inexact, overflow) = round_f64_to_f32(f64::MAX, RoundingMode::RoundNearestEven);
+        assert!(value.is_infinite() && value.is_sign_positive());
+        assert!(inexact && overflow);
+    }
+
+    fn r_type(opcode: u32, rd: u32, funct3: u32, rs1: u32, rs2: u32, funct7: u32) -> u32 {
+        (funct7 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+    }
+
+    #[test]
+    fn decode_fp_recognizes_core_op_fp_arithmetic() {
+        // fadd.s f1, f2, f3, rne
+        let word = r_type(0b1010011, 1, 0b000, 2, 3, 0b0000000);
+        assert_eq!(
+            decode_fp(word),
+            Some(FpInstruction::Op { op: FpOp::Add, rd: 1, rs1: 2, rs2: 3, rm: 0 })
+        );
+
+        // fdiv.s f1, f2, f3, rtz
+        let word = r_type(0b1010011, 1, 0b001, 2, 3, 0b0001100);
+        assert_eq!(
+            decode_fp(word),
+            Some(FpInstruction::Op { op: FpOp::Div, rd: 1, rs1: 2, rs2: 3, rm: 1 })
+        );
+
+        // fsqrt.s f1, f2, rne (rs2 must be 0)
+        let word = r_type(0b1010011, 1, 0b000, 2, 0, 0b0101100);
+        assert_eq!(
+            decode_fp(word),
+            Some(FpInstruction::Op { op: FpOp::Sqrt, rd: 1, rs1: 2, rs2: 0, rm: 0 })
+        );
+    }
+
+    #[test]
+    fn decode_fp_recognizes_compare_class_and_convert() {
+        // feq.s x1, f2, f3
+        let word = r_type(0b1010011, 1, 0b010, 2, 3, 0b1010000);
+        assert_eq!(decode_fp(word), Some(FpInstruction::Op { op: FpOp::Eq, rd: 1, rs1: 2, rs2: 3, rm: 0 }));
+
+        // fclass.s x1, f2
+        let word = r_type(0b1010011, 1, 0b001, 2, 0, 0b1110000);
+        assert_eq!(
+            decode_fp(word),
+            Some(FpInstruction::Op { op: FpOp::Class, rd: 1, rs1: 2, rs2: 0, rm: 0 })
+        );
+
+        // fcvt.wu.s x1, f2, rdn
+        let word = r_type(0b1010011, 1, 0b010, 2, 0b00001, 0b1100000);
+        assert_eq!(
+            decode_fp(word),
+            Some(FpInstruction::Op { op: FpOp::CvtWuS, rd: 1, rs1: 2, rs2: 1, rm: 0b010 })
+        );
+    }
+
+    #[test]
+    fn decode_fp_rejects_double_precision_fmt() {
+        // Same bit pattern as fadd.s but fmt = 01 (.d): out of scope.
+        let word = r_type(0b1010011, 1, 0b000, 2, 3, 0b0000001);
+        assert_eq!(decode_fp(word), None);
+    }
+
+    #[test]
+    fn decode_fp_recognizes_load_store_and_fma() {
+        // flw f1, 0x64(x2)
+        let word = (0x64u32 << 20) | (2 << 15) | (0b010 << 12) | (1 << 7) | 0b0000111;
+        assert_eq!(decode_fp(word), Some(FpInstruction::Load { rd: 1, rs1: 2, imm: 0x64 }));
+
+        // fsw f3, 0x64(x2)
+        let imm_hi = (0x64u32 >> 5) & 0x7F;
+        let imm_lo = 0x64u32 & 0x1F;
+        let word =
+            (imm_hi << 25) | (3 << 20) | (2 << 15) | (0b010 << 12) | (imm_lo << 7) | 0b0100111;
+        assert_eq!(decode_fp(word), Some(FpInstruction::Store { rs1: 2, rs2: 3, imm: 0x64 }));
+
+        // fmadd.s f1, f2, f3, f4, rdn
+        let word = (4u32 << 27) | (3 << 20) | (2 << 15) | (0b010 << 12) | (1 << 7) | 0b1000011;
+        assert_eq!(
+            decode_fp(word),
+            Some(FpInstruction::Fma { op: FmaOp::Madd, rd: 1, rs1: 2, rs2: 3, rs3: 4, rm: 0b010 })
+        );
+    }
+
+    #[test]
+    fn execute_fp_add_sets_fflags_and_writes_the_destination_register() {
+        let mut state = ExecutionState::new(0);
+        state.set_f32(2, 1.0);
+        state.set_f32(3, 2.0);
+
+        state
+            .execute_fp(FpInstruction::Op { op: FpOp::Add, rd: 1, rs1: 2, rs2: 3, rm: 0 })
+            .unwrap();
+
+        assert_eq!(state.get_f32(1), 3.0);
+        assert_eq!(state.csrs.fflags(), 0);
+    }
+
+    #[test]
+    fn execute_fp_div_by_zero_sets_dz_flag_and_yields_infinity() {
+        let mut state = ExecutionState::new(0);
+        state.set_f32(2, 1.0);
+        state.set_f32(3, 0.0);
+
+        state
+            .execute_fp(FpInstruction::Op { op: FpOp::Div, rd: 1, rs1: 2, rs2: 3, rm: 0 })
+            .unwrap();
+
+        assert!(state.get_f32(1).is_infinite());
+        assert_eq!(state.csrs.fflags() & FFLAG_DZ, FFLAG_DZ);
+    }
+
+    #[test]
+    fn execute_fp_div_zero_by_zero_sets_nv_and_yields_canonical_nan() {
+        let mut state = ExecutionState::new(0);
+        state.set_f32(2, 0.0);
+        state.set_f32(3, 0.0);
+
+        state
+            .execute_fp(FpInstruction::Op { op: FpOp::Div, rd: 1, rs1: 2, rs2: 3, rm: 0 })
+            .unwrap();
+
+        assert_eq!(state.get_f32(1).to_bits(), CANONICAL_NAN_F32);
+        assert_eq!(state.csrs.fflags() & FFLAG_NV, FFLAG_NV);
+    }
+
+    #[test]
+    fn execute_fp_sub_infinity_minus_infinity_sets_nv() {
+        let mut state = ExecutionState::new(0);
+        state.set_f32(2, f32::INFINITY);
+        state.set_f32(3, f32::INFINITY);
+
+        state
+            .execute_fp(FpInstruction::Op { op: FpOp::Sub, rd: 1, rs1: 2, rs2: 3, rm: 0 })
+            .unwrap();
+
+        assert_eq!(state.get_f32(1).to_bits(), CANONICAL_NAN_F32);
+        assert_eq!(state.csrs.fflags() & FFLAG_NV, FFLAG_NV);
+    }
+
+    #[test]
+    fn execute_fp_sqrt_of_negative_is_canonical_nan_with_nv_set() {
+        let mut state = ExecutionState::new(0);
+        state.set_f32(2, -4.0);
+
+        state.execute_fp(FpInstruction::Op { op: FpOp::Sqrt, rd: 1, rs1: 2, rs2: 0, rm: 0 }).unwrap();
+
+        assert_eq!(state.get_f32(1).to_bits(), CANONICAL_NAN_F32);
+        assert_eq!(state.csrs.fflags() & FFLAG_NV, FFLAG_NV);
+    }
+
+    #[test]
+    fn execute_fp_compares_and_fclass() {
+        let mut state = ExecutionState::new(0);
+        state.set_f32(1, 1.0);
+        state.set_f32(2, 2.0);
+
+        state.execute_fp(FpInstruction::Op { op: FpOp::Lt, rd: 5, rs1: 1, rs2: 2, rm: 0 }).unwrap();
+        assert_eq!(state.get_register(5).value, 1);
+
+        state.execute_fp(FpInstruction::Op { op: FpOp::Class, rd: 6, rs1: 1, rs2: 0, rm: 0 }).unwrap();
+        assert_eq!(state.get_register(6).value, 1 << 6); // positive normal
+    }
+
+    #[test]
+    fn execute_fp_cvt_w_s_saturates_out_of_range_inputs() {
+        let mut state = ExecutionState::new(0);
+        state.set_f32(1, 1.0e30);
+
+        state
+            .execute_fp(FpInstruction::Op { op: FpOp::CvtWS, rd: 5, rs1: 1, rs2: 0, rm: 0 })
+            .unwrap();
+
+        assert_eq!(state.get_register(5).value, i32::MAX as u32);
+        assert_eq!(state.csrs.fflags() & FFLAG_NV, FFLAG_NV);
+    }
+
+    #[test]
+    fn execute_fp_cvt_s_w_round_trips_a_small_integer() {
+        let mut state = ExecutionState::new(0);
+        state.set_register(1, MemoryRecord { value: 7u32.wrapping_neg(), ..Default::default() });
+
+        state
+            .execute_fp(FpInstruction::Op { op: FpOp::CvtSW, rd: 5, rs1: 1, rs2: 0, rm: 0 })
+            .unwrap();
+
+        assert_eq!(state.get_f32(5), -7.0);
+    }
+
+    #[test]
+    fn execute_fp_fmadd_computes_a_times_b_plus_c() {
+        let mut state = ExecutionState::new(0);
+        state.set_f32(1, 2.0);
+        state.set_f32(2, 3.0);
+        state.set_f32(3, 4.0);
+
+        state
+            .execute_fp(FpInstruction::Fma { op: FmaOp::Madd, rd: 5, rs1: 1, rs2: 2, rs3: 3, rm: 0 })
+            .unwrap();
+
+        assert_eq!(state.get_f32(5), 10.0);
+    }
+
+    #[test]
+    fn execute_fp_fmadd_infinity_times_zero_sets_nv() {
+        let mut state = ExecutionState::new(0);
+        state.set_f32(1, f32::INFINITY);
+        state.set_f32(2, 0.0);
+        state.set_f32(3, 1.0);
+
+        state
+            .execute_fp(FpInstruction::Fma { op: FmaOp::Madd, rd: 5, rs1: 1, rs2: 2, rs3: 3, rm: 0 })
+            .unwrap();
+
+        assert_eq!(state.get_f32(5).to_bits(), CANONICAL_NAN_F32);
+        assert_eq!(state.csrs.fflags() & FFLAG_NV, FFLAG_NV);
+    }
+
+    #[test]
+    fn execute_fp_rejects_reserved_rounding_mode() {
+        let mut state = ExecutionState::new(0);
+        state.set_f32(1, 1.0);
+        state.set_f32(2, 2.0);
+
+        let result = state.execute_fp(FpInstruction::Op {
+            op: FpOp::Add,
+            rd: 3,
+            rs1: 1,
+            rs2: 2,
+            rm: 0b101, // reserved
+        });
+
+        assert_eq!(result, Err(TrapCause::IllegalInstruction));
+    }
+
+    #[test]
+    fn execute_fp_load_and_store_round_trip_through_memory() {
+        let mut state = ExecutionState::new(0);
+        state.set_register(1, MemoryRecord { value: 100, ..Default::default() });
+        state.set_f32(2, 1.5);
+
+        state.execute_fp(FpInstruction::Store { rs1: 1, rs2: 2, imm: 0 }).unwrap();
+        state.execute_fp(FpInstruction::Load { rd: 3, rs1: 1, imm: 0 }).unwrap();
+
+        assert_eq!(state.get_f32(3), 1.5);
+    }
+}
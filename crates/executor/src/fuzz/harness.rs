@@ -0,0 +1,118 @@
+//! Wires [`FuzzProgram`] generation and the [`oracle`](super::oracle) up to
+//! `Executor::run`, for use by the `fuzz` crate's `differential` target.
+
+use super::oracle::{self, FuzzOutcome};
+use super::program::{FuzzProgram, MAX_FUZZ_CLOCK};
+use crate::events::MemoryRecord;
+use crate::{Executor, MemoryBackend, Program};
+
+/// A divergence between `Executor::run` and the reference oracle.
+#[derive(Debug)]
+pub struct Divergence {
+    /// The program that produced the mismatch.
+    pub program: FuzzProgram,
+    /// The first memory address where the two outcomes disagree, along with
+    /// the executor's and the oracle's value at that address.
+    pub first_mismatch: Option<(u32, u32, u32)>,
+    /// The full state produced by `Executor::run`.
+    pub executor_outcome: FuzzOutcome,
+    /// The full state produced by the reference oracle.
+    pub oracle_outcome: FuzzOutcome,
+}
+
+/// Run `program` through both `Executor::run` and the reference oracle and
+/// compare the resulting registers, `pc`, and touched memory.
+///
+/// # Panics
+///
+/// Panics if `Executor::run` panics; callers driving this from `cargo fuzz`
+/// want that panic to be reported as a crash, not converted into `Err`.
+pub fn run_differential(program: FuzzProgram) -> Result<FuzzOutcome, Box<Divergence>> {
+    let executor_outcome = run_executor(&program);
+    let oracle_outcome = oracle::run(&program);
+
+    if executor_outcome == oracle_outcome {
+        return Ok(executor_outcome);
+    }
+
+    // Line the two sides up by address rather than by position, reading an
+    // address only one side touched as `0` on the other.
+    let exec_mem: std::collections::BTreeMap<u32, u32> =
+        executor_outcome.touched_memory.iter().copied().collect();
+    let oracle_mem: std::collections::BTreeMap<u32, u32> =
+        oracle_outcome.touched_memory.iter().copied().collect();
+    let first_mismatch = exec_mem
+        .keys()
+        .chain(oracle_mem.keys())
+        .copied()
+        .collect::<std::collections::BTreeSet<u32>>()
+        .into_iter()
+        .map(|addr| (addr, exec_mem.get(&addr).copied().unwrap_or(0), oracle_mem.get(&addr).copied().unwrap_or(0)))
+        .find(|&(_, exec_val, oracle_val)| exec_val != oracle_val);
+
+    Err(Box::new(Divergence { program, first_mismatch, executor_outcome, oracle_outcome }))
+}
+
+/// Execute `program` through the real interpreter and normalize its final
+/// state into a [`FuzzOutcome`].
+fn run_executor(program: &FuzzProgram) -> FuzzOutcome {
+    let mut executor = Executor::new(Program::new(program.instructions.clone(), 0, 0));
+    for &(addr, value) in &program.seed_memory {
+        executor.state.write_memory(addr, MemoryRecord { value, ..Default::default() });
+    }
+
+    // `Executor::run`'s own bound is a cycle count, not a halt condition, so
+    // a generated program that loops forever (e.g. a backward branch whose
+    // condition keeps holding) still terminates here instead of hanging the
+    // fuzzer.
+    let _ = executor.run(MAX_FUZZ_CLOCK);
+
+    let mut registers = [0u32; 32];
+    for i in 0..8 {
+        registers[i] = executor.state.get_register(i).value;
+    }
+    for i in 8..32 {
+        registers[i] = executor.state.get_register(i).value;
+    }
+
+    // `PagedMemory::iter` yields every word of every allocated *page*
+    // (`PAGE_WORDS` at a time), while the oracle's `touched_memory` only ever
+    // contains addresses it actually seeded or wrote. Use the word-level
+    // dirty tracker instead so the two sides are comparable.
+    let mut touched_memory: Vec<(u32, u32)> = executor
+        .state
+        .memory
+        .touched_addresses()
+        .map(|addr| (addr, executor.state.memory.read(addr).value))
+        .collect();
+    touched_memory.sort_unstable_by_key(|&(addr, _)| addr);
+
+    FuzzOutcome { registers, pc: executor.state.pc, touched_memory }
+}
+
+/// Re-run a previously minimized failing case deterministically.
+///
+/// `program_bytes` is the raw fuzzer input, as `cargo fuzz` saves it in the
+/// `differential` target's corpus and crash artifacts; it is decoded into a
+/// [`FuzzProgram`] the same way the target decodes its input.
+pub fn replay(program_bytes: &[u8]) -> Result<FuzzOutcome, Box<Divergence>> {
+    let program =
+        <FuzzProgram as arbitrary::Arbitrary>::arbitrary_take_rest(arbitrary::Unstructured::new(program_bytes))
+            .expect("corpus entry is not a valid FuzzProgram");
+    run_differential(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_nop_program_does_not_diverge() {
+        // `addi x0, x0, 0`, i.e. a NOP that writes nothing.
+        let program = FuzzProgram { instructions: vec![0x0000_0013], seed_memory: Vec::new() };
+
+        let outcome = run_differential(program).expect("executor and oracle must agree on a NOP");
+        assert_eq!(outcome.registers, [0u32; 32]);
+        assert!(outcome.touched_memory.is_empty());
+    }
+}
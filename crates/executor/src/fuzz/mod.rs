@@ -0,0 +1,16 @@
+//! A differential fuzzing harness for [`crate::Executor`].
+//!
+//! Randomly generated but well-formed RV32IM instruction streams ([`program`])
+//! are executed through the real interpreter and through an independent,
+//! deliberately simple [`oracle`], and their final state is compared by
+//! [`run_differential`]. This is only compiled when fuzzing, since the
+//! generated programs and the oracle are not part of the executor's public
+//! surface.
+
+mod harness;
+mod oracle;
+mod program;
+
+pub use harness::*;
+pub use oracle::FuzzOutcome;
+pub use program::*;
@@ -0,0 +1,140 @@
+//! An independent, intentionally simple RV32I reference model.
+//!
+//! This does not share any decoding or execution code with [`crate::Executor`]
+//! — it exists purely so [`super::run_differential`] has something to compare
+//! against that could not have inherited a bug from the optimized path.
+
+use super::program::{FuzzProgram, MAX_FUZZ_CLOCK};
+
+/// The state produced by running a [`FuzzProgram`] through the oracle or
+/// through [`crate::Executor`], normalized so the two are directly
+/// comparable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzOutcome {
+    /// Final values of `x0..=x31`.
+    pub registers: [u32; 32],
+    /// Final program counter.
+    pub pc: u32,
+    /// Every memory address seeded before or written during execution (loads
+    /// are not recorded), in ascending order, paired with its final value.
+    pub touched_memory: Vec<(u32, u32)>,
+}
+
+/// Run `program` through the reference model.
+pub fn run(program: &FuzzProgram) -> FuzzOutcome {
+    let mut registers = [0u32; 32];
+    let mut memory = std::collections::BTreeMap::new();
+    for &(addr, value) in &program.seed_memory {
+        memory.insert(addr, value);
+    }
+
+    let mut pc = 0u32;
+    let text_len = (program.instructions.len() as u32) * 4;
+    let mut clock = 0u64;
+
+    while clock < MAX_FUZZ_CLOCK && pc < text_len {
+        let word = program.instructions[(pc / 4) as usize];
+        let next_pc = step(word, pc, &mut registers, &mut memory);
+        pc = next_pc;
+        clock += 1;
+    }
+
+    FuzzOutcome { registers, pc, touched_memory: memory.into_iter().collect() }
+}
+
+/// Decode and execute a single instruction word, returning the next `pc`.
+fn step(word: u32, pc: u32, registers: &mut [u32; 32], memory: &mut std::collections::BTreeMap<u32, u32>) -> u32 {
+    let opcode = word & 0x7F;
+    let rd = ((word >> 7) & 0x1F) as usize;
+    let funct3 = (word >> 12) & 0x7;
+    let rs1 = ((word >> 15) & 0x1F) as usize;
+    let rs2 = ((word >> 20) & 0x1F) as usize;
+    let funct7 = (word >> 25) & 0x7F;
+
+    let write = |registers: &mut [u32; 32], reg: usize, value: u32| {
+        if reg != 0 {
+            registers[reg] = value;
+        }
+    };
+
+    match opcode {
+        // R-type: ADD/SUB/AND/OR/XOR
+        0b0110011 => {
+            let a = registers[rs1];
+            let b = registers[rs2];
+            let result = match (funct3, funct7) {
+                (0b000, 0b0000000) => a.wrapping_add(b),
+                (0b000, 0b0100000) => a.wrapping_sub(b),
+                (0b111, _) => a & b,
+                (0b110, _) => a | b,
+                (0b100, _) => a ^ b,
+                _ => a,
+            };
+            write(registers, rd, result);
+            pc.wrapping_add(4)
+        }
+        // I-type: ADDI/SLTI
+        0b0010011 => {
+            let imm = sign_extend(word >> 20, 12);
+            let a = registers[rs1] as i32;
+            let result = match funct3 {
+                0b000 => a.wrapping_add(imm),
+                0b010 => i32::from(a < imm),
+                _ => a,
+            };
+            write(registers, rd, result as u32);
+            pc.wrapping_add(4)
+        }
+        // LW
+        0b0000011 => {
+            let imm = sign_extend(word >> 20, 12);
+            let addr = (registers[rs1] as i32).wrapping_add(imm) as u32;
+            write(registers, rd, *memory.get(&addr).unwrap_or(&0));
+            pc.wrapping_add(4)
+        }
+        // SW
+        0b0100011 => {
+            let imm_lo = (word >> 7) & 0x1F;
+            let imm_hi = (word >> 25) & 0x7F;
+            let imm = sign_extend((imm_hi << 5) | imm_lo, 12);
+            let addr = (registers[rs1] as i32).wrapping_add(imm) as u32;
+            memory.insert(addr, registers[rs2]);
+            pc.wrapping_add(4)
+        }
+        // BEQ/BNE
+        0b1100011 => {
+            let b12 = (word >> 31) & 0x1;
+            let b11 = (word >> 7) & 0x1;
+            let b10_5 = (word >> 25) & 0x3F;
+            let b4_1 = (word >> 8) & 0xF;
+            let imm = sign_extend((b12 << 12) | (b11 << 11) | (b10_5 << 5) | (b4_1 << 1), 13);
+            let taken = match funct3 {
+                0b000 => registers[rs1] == registers[rs2],
+                0b001 => registers[rs1] != registers[rs2],
+                _ => false,
+            };
+            if taken {
+                (pc as i32).wrapping_add(imm) as u32
+            } else {
+                pc.wrapping_add(4)
+            }
+        }
+        // JAL
+        0b1101111 => {
+            let b20 = (word >> 31) & 0x1;
+            let b19_12 = (word >> 12) & 0xFF;
+            let b11 = (word >> 20) & 0x1;
+            let b10_1 = (word >> 21) & 0x3FF;
+            let imm = sign_extend((b20 << 20) | (b19_12 << 12) | (b11 << 11) | (b10_1 << 1), 21);
+            write(registers, rd, pc.wrapping_add(4));
+            (pc as i32).wrapping_add(imm) as u32
+        }
+        _ => pc.wrapping_add(4),
+    }
+}
+
+/// Sign-extend the low `bits` bits of `value` to a full `i32`.
+fn sign_extend(value: u32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    ((value << shift) as i32) >> shift
+}
@@ -0,0 +1,130 @@
+use arbitrary::{Arbitrary, Unstructured};
+use serde::{Deserialize, Serialize};
+
+/// Upper bound on `global_clk` for a single fuzz run.
+///
+/// Generated programs can contain backward branches, so without a bound a
+/// pathological input could loop forever instead of reporting a mismatch.
+pub const MAX_FUZZ_CLOCK: u64 = 1 << 16;
+
+/// Number of `u32` words of memory seeded before execution starts.
+const SEED_WORDS: usize = 64;
+
+/// A small, deliberately restricted subset of RV32IM covered by the
+/// generator today. Extending this list is the main way to widen coverage.
+#[derive(Debug, Clone, Copy, Arbitrary)]
+enum FuzzOp {
+    AddReg,
+    SubReg,
+    AndReg,
+    OrReg,
+    XorReg,
+    AddImm,
+    SltiImm,
+    LoadWord,
+    StoreWord,
+    BranchEq,
+    BranchNe,
+    Jal,
+}
+
+/// A randomly generated, well-formed RV32IM instruction stream plus the
+/// memory it should be seeded with before execution.
+///
+/// Both the real executor and the [`super::oracle`] run from the same
+/// `instructions`/`seed_memory`, so a divergence between them reflects a
+/// genuine bug rather than a difference in setup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FuzzProgram {
+    /// Raw RV32IM instruction words, ready to be loaded at `pc = 0`.
+    pub instructions: Vec<u32>,
+    /// `(address, value)` pairs written into memory before execution, in a
+    /// fixed, deterministic order so both sides see the same initial state.
+    pub seed_memory: Vec<(u32, u32)>,
+}
+
+impl<'a> Arbitrary<'a> for FuzzProgram {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let instruction_count = u.int_in_range(1..=256)?;
+        let mut instructions = Vec::with_capacity(instruction_count);
+        for _ in 0..instruction_count {
+            let op = FuzzOp::arbitrary(u)?;
+            let rd = u.int_in_range(1..=31)?;
+            let rs1 = u.int_in_range(0..=31)?;
+            let rs2 = u.int_in_range(0..=31)?;
+            // Keep branch/jump offsets small and word-aligned so they mostly
+            // land inside the generated instruction stream; one that lands
+            // outside it ends the run.
+            let mut rel_words: i32 = u.int_in_range(-8..=8)?;
+            // A zero offset on a branch/jump is a self-loop: `Jal` always
+            // takes it, and a branch whose condition holds re-checks the same
+            // unchanged registers every time, so either would run until
+            // `MAX_FUZZ_CLOCK` stops `Executor::run`. Bump it off zero so these ops always
+            // make forward (or backward) progress.
+            if matches!(op, FuzzOp::BranchEq | FuzzOp::BranchNe | FuzzOp::Jal) && rel_words == 0 {
+                rel_words = 1;
+            }
+            let imm12: i32 = u.int_in_range(-2048..=2047)?;
+            instructions.push(encode(op, rd, rs1, rs2, rel_words, imm12));
+        }
+
+        let mut seed_memory = Vec::with_capacity(SEED_WORDS);
+        for i in 0..SEED_WORDS {
+            seed_memory.push((i as u32 * 4, u32::arbitrary(u)?));
+        }
+
+        Ok(Self { instructions, seed_memory })
+    }
+}
+
+/// Encode one [`FuzzOp`] into its RV32IM instruction word.
+fn encode(op: FuzzOp, rd: u32, rs1: u32, rs2: u32, rel_words: i32, imm12: i32) -> u32 {
+    let imm12 = (imm12 as u32) & 0xFFF;
+    match op {
+        FuzzOp::AddReg => r_type(0b0110011, 0b000, 0b0000000, rd, rs1, rs2),
+        FuzzOp::SubReg => r_type(0b0110011, 0b000, 0b0100000, rd, rs1, rs2),
+        FuzzOp::AndReg => r_type(0b0110011, 0b111, 0b0000000, rd, rs1, rs2),
+        FuzzOp::OrReg => r_type(0b0110011, 0b110, 0b0000000, rd, rs1, rs2),
+        FuzzOp::XorReg => r_type(0b0110011, 0b100, 0b0000000, rd, rs1, rs2),
+        FuzzOp::AddImm => i_type(0b0010011, 0b000, rd, rs1, imm12),
+        FuzzOp::SltiImm => i_type(0b0010011, 0b010, rd, rs1, imm12),
+        FuzzOp::LoadWord => i_type(0b0000011, 0b010, rd, rs1, imm12 & 0x0FF),
+        FuzzOp::StoreWord => s_type(0b0100011, 0b010, rs1, rs2, imm12 & 0x0FF),
+        FuzzOp::BranchEq => b_type(0b1100011, 0b000, rs1, rs2, rel_words * 4),
+        FuzzOp::BranchNe => b_type(0b1100011, 0b001, rs1, rs2, rel_words * 4),
+        FuzzOp::Jal => j_type(0b1101111, rd, rel_words * 4),
+    }
+}
+
+fn r_type(opcode: u32, funct3: u32, funct7: u32, rd: u32, rs1: u32, rs2: u32) -> u32 {
+    (funct7 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+
+fn i_type(opcode: u32, funct3: u32, rd: u32, rs1: u32, imm12: u32) -> u32 {
+    ((imm12 & 0xFFF) << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+
+fn s_type(opcode: u32, funct3: u32, rs1: u32, rs2: u32, imm12: u32) -> u32 {
+    let imm12 = imm12 & 0xFFF;
+    let imm_hi = (imm12 >> 5) & 0x7F;
+    let imm_lo = imm12 & 0x1F;
+    (imm_hi << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (imm_lo << 7) | opcode
+}
+
+fn b_type(opcode: u32, funct3: u32, rs1: u32, rs2: u32, offset: i32) -> u32 {
+    let imm = offset as u32;
+    let b12 = (imm >> 12) & 0x1;
+    let b11 = (imm >> 11) & 0x1;
+    let b10_5 = (imm >> 5) & 0x3F;
+    let b4_1 = (imm >> 1) & 0xF;
+    (b12 << 31) | (b10_5 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (b4_1 << 8) | (b11 << 7) | opcode
+}
+
+fn j_type(opcode: u32, rd: u32, offset: i32) -> u32 {
+    let imm = offset as u32;
+    let b20 = (imm >> 20) & 0x1;
+    let b19_12 = (imm >> 12) & 0xFF;
+    let b11 = (imm >> 11) & 0x1;
+    let b10_1 = (imm >> 1) & 0x3FF;
+    (b20 << 31) | (b10_1 << 21) | (b11 << 20) | (b19_12 << 12) | (rd << 7) | opcode
+}
@@ -20,9 +20,14 @@
 #![warn(missing_docs)]
 
 mod context;
+mod csr;
 mod disassembler;
 pub mod events;
 mod executor;
+mod fork;
+mod fpu;
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
 mod hook;
 mod instruction;
 mod io;
@@ -37,7 +42,9 @@ mod utils;
 mod utils_simd;
 
 pub use context::*;
+pub use csr::*;
 pub use executor::*;
+pub use fpu::*;
 pub use hook::*;
 pub use instruction::*;
 pub use opcode::*;
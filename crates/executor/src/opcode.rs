@@ -0,0 +1,20 @@
+//! Base RV32I opcode constants shared by [`crate::instruction`]'s decoder.
+//!
+//! `csr` and `fpu` decode their own `SYSTEM`/`OP-FP`-family opcodes locally
+//! since those are only ever reached from one place each; these are the ones
+//! [`crate::instruction::decode`] itself switches on.
+
+/// `ADD`/`SUB`/`AND`/`OR`/`XOR` (R-type).
+pub const OPCODE_OP: u32 = 0b0110011;
+/// `ADDI`/`SLTI` (I-type).
+pub const OPCODE_OP_IMM: u32 = 0b0010011;
+/// `LW`.
+pub const OPCODE_LOAD: u32 = 0b0000011;
+/// `SW`.
+pub const OPCODE_STORE: u32 = 0b0100011;
+/// `BEQ`/`BNE` (B-type).
+pub const OPCODE_BRANCH: u32 = 0b1100011;
+/// `JAL` (J-type).
+pub const OPCODE_JAL: u32 = 0b1101111;
+/// `ECALL`/`EBREAK`, and the `csrrw`/`csrrs`/`csrrc` family (see [`crate::csr`]).
+pub const OPCODE_SYSTEM: u32 = 0b1110011;
@@ -0,0 +1,34 @@
+//! The static program image an [`crate::Executor`] runs.
+
+/// A program image: raw instruction words loaded contiguously at a fixed
+/// base address, plus the bound execution runs within.
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    /// Raw instruction words, loaded contiguously starting at `pc_start`.
+    pub instructions: Vec<u32>,
+    /// The program counter execution starts at.
+    pub pc_start: u32,
+    /// The program counter execution halts at (exclusive). `0` means "run
+    /// until `pc` reaches the end of `instructions`", since a real program
+    /// never has its `.text` end at address zero.
+    pub pc_end: u32,
+}
+
+impl Program {
+    /// Build a [`Program`] from raw instruction words.
+    #[must_use]
+    pub fn new(instructions: Vec<u32>, pc_start: u32, pc_end: u32) -> Self {
+        Self { instructions, pc_start, pc_end }
+    }
+
+    /// The exclusive upper bound execution runs within: `pc_end` if set,
+    /// otherwise the address just past the last loaded instruction.
+    #[must_use]
+    pub fn text_end(&self) -> u32 {
+        if self.pc_end != 0 {
+            self.pc_end
+        } else {
+            self.pc_start.wrapping_add((self.instructions.len() as u32) * 4)
+        }
+    }
+}
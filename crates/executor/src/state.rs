@@ -3,27 +3,178 @@ use std::{
     io::{Seek, Write},
 };
 
-use hashbrown::HashMap;
-use serde::{self, Deserialize, Serialize};
-
-use serde_big_array::BigArray;
-use crate::{events::MemoryRecord, syscalls::SyscallCode, ExecutorMode};
+use hashbrown::{HashMap, HashSet};
+use serde::{
+    self,
+    de::{SeqAccess, Visitor},
+    ser::SerializeSeq,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 
+use crate::{events::MemoryRecord, syscalls::SyscallCode, CsrFile, ExecutorMode};
 
-// 2GB memory space for the program with 32 bit address space
 /// The maximum number of memory addresses that can be tracked.
+///
+/// This is a logical bound on the 32-bit address space, not a physical
+/// allocation: [`PagedMemory`] only materializes the pages a program
+/// actually touches.
 pub const MAXIMUM_ADDRESSES: usize = 1 << 29;
 
+/// The number of 32-bit words held by a single memory page (4 KiB).
+pub const PAGE_WORDS: usize = 1024;
+
+/// A single lazily-allocated page of memory.
+pub type Page = Box<[MemoryRecord; PAGE_WORDS]>;
+
+/// A pluggable backing store for a program's memory.
+///
+/// The executor only ever talks to memory through this trait, so alternate
+/// backends (e.g. one backed by memory-mapped devices) can be swapped in
+/// without touching the instruction-execution code. [`PagedMemory`] is the
+/// default implementation.
+pub trait MemoryBackend {
+    /// Read the record at `addr`. Addresses whose page has never been
+    /// written return a default (zeroed) record.
+    fn read(&self, addr: u32) -> MemoryRecord;
+
+    /// Write `record` to `addr`, allocating the backing page on first write.
+    fn write(&mut self, addr: u32, record: MemoryRecord);
+
+    /// Iterate over every address in every page that has been allocated.
+    ///
+    /// Untouched pages are skipped entirely, so serializing this iterator
+    /// only persists memory the program actually wrote to.
+    fn iter(&self) -> Box<dyn Iterator<Item = (u32, MemoryRecord)> + '_>;
+}
+
 /// The memory of the program.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Memory(
-    #[serde(with = "BigArray")]
-    pub [MemoryRecord; MAXIMUM_ADDRESSES]
-);
+///
+/// Backed by a `HashMap` of lazily-allocated 4 KiB pages rather than one
+/// flat `2 GB` array, so a small program no longer pays for the full
+/// [`MAXIMUM_ADDRESSES`] address space up front. This also makes
+/// `ExecutionState::clone`/`save` proportional to the memory actually
+/// touched instead of the whole address space.
+#[derive(Debug, Clone, Default)]
+pub struct PagedMemory {
+    pages: HashMap<u32, Page>,
+    /// Every address ever passed to [`PagedMemory::write`], independent of
+    /// page-level allocation granularity.
+    ///
+    /// `iter` (and therefore save/load) intentionally walks whole pages, but
+    /// some callers — e.g. the differential fuzzer in `fuzz::harness`, which
+    /// compares its touched set against a reference oracle that only ever
+    /// sees actually-written addresses — need the finer-grained answer to
+    /// "was this exact address written?" instead of "was this address's page
+    /// allocated?". Not persisted; see [`PagedMemory::touched_addresses`].
+    touched: HashSet<u32>,
+}
+
+// `serde`'s derive only implements `Serialize`/`Deserialize` for arrays up to
+// 32 elements, so a derived impl can't reach through `Page`'s 1024-element
+// array the way the old flat `Memory` needed `serde_big_array::BigArray` for
+// its single giant array. Here we instead serialize each page as a `Vec`,
+// which keeps the on-disk format a plain list of (page number, page
+// contents) pairs without pulling in an extra dependency for such a small
+// surface.
+impl Serialize for PagedMemory {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.pages.len()))?;
+        for (page, records) in &self.pages {
+            seq.serialize_element(&(*page, records.as_slice()))?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for PagedMemory {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PagedMemoryVisitor;
+
+        impl<'de> Visitor<'de> for PagedMemoryVisitor {
+            type Value = PagedMemory;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a sequence of (page number, page contents) pairs")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut pages = HashMap::new();
+                while let Some((page, records)) = seq.next_element::<(u32, Vec<MemoryRecord>)>()? {
+                    let boxed: Page = records.into_boxed_slice().try_into().map_err(|_| {
+                        serde::de::Error::custom("page did not contain PAGE_WORDS records")
+                    })?;
+                    pages.insert(page, boxed);
+                }
+                Ok(PagedMemory { pages, touched: HashSet::new() })
+            }
+        }
+
+        deserializer.deserialize_seq(PagedMemoryVisitor)
+    }
+}
+
+impl PagedMemory {
+    /// Split `addr` into a page number and the word offset within that page.
+    fn locate(addr: u32) -> (u32, usize) {
+        let word = addr / 4;
+        (word / PAGE_WORDS as u32, (word % PAGE_WORDS as u32) as usize)
+    }
+
+    /// Borrow page number `page` as a contiguous slice, allocating it if
+    /// necessary. Used by bulk operations (see `utils_simd`) that need a
+    /// contiguous run of records to vectorize over.
+    pub fn page_mut(&mut self, page: u32) -> &mut [MemoryRecord; PAGE_WORDS] {
+        self.pages.entry(page).or_insert_with(|| Box::new([MemoryRecord::default(); PAGE_WORDS]))
+    }
 
-impl Default for Memory {
-    fn default() -> Self {
-        Self([MemoryRecord::default(); MAXIMUM_ADDRESSES])
+    /// Borrow page number `page`, if it has been allocated.
+    pub fn page(&self, page: u32) -> Option<&[MemoryRecord; PAGE_WORDS]> {
+        self.pages.get(&page).map(Box::as_ref)
+    }
+
+    /// Whether the page containing `addr` has ever been allocated, i.e.
+    /// whether any address on that page has been written (or the page was
+    /// borrowed through [`PagedMemory::page_mut`]). See
+    /// [`PagedMemory::is_touched`] for `addr` itself.
+    #[must_use]
+    pub fn is_allocated(&self, addr: u32) -> bool {
+        let (page, _) = Self::locate(addr);
+        self.pages.contains_key(&page)
+    }
+
+    /// Whether `addr` itself has ever been passed to [`PagedMemory::write`],
+    /// as opposed to merely sharing a page with an address that was (see
+    /// [`PagedMemory::is_allocated`]).
+    #[must_use]
+    pub fn is_touched(&self, addr: u32) -> bool {
+        self.touched.contains(&addr)
+    }
+
+    /// Every address ever passed to [`PagedMemory::write`], in no particular
+    /// order. Unlike [`PagedMemory::iter`], this excludes the untouched rest
+    /// of an allocated page.
+    pub fn touched_addresses(&self) -> impl Iterator<Item = u32> + '_ {
+        self.touched.iter().copied()
+    }
+}
+
+impl MemoryBackend for PagedMemory {
+    fn read(&self, addr: u32) -> MemoryRecord {
+        let (page, offset) = Self::locate(addr);
+        self.pages.get(&page).map_or_else(MemoryRecord::default, |records| records[offset])
+    }
+
+    fn write(&mut self, addr: u32, record: MemoryRecord) {
+        let (page, offset) = Self::locate(addr);
+        self.page_mut(page)[offset] = record;
+        self.touched.insert(addr);
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (u32, MemoryRecord)> + '_> {
+        Box::new(self.pages.iter().flat_map(|(&page, records)| {
+            let base = page * PAGE_WORDS as u32;
+            records.iter().enumerate().map(move |(offset, record)| (base + offset as u32, *record))
+        }))
     }
 }
 
@@ -39,7 +190,7 @@ pub struct ExecutionState {
 
     /// The memory which instructions operate over. Values contain the memory value and last shard
     /// + timestamp that each memory address was accessed.
-    pub memory: Box<Memory>,
+    pub memory: Box<PagedMemory>,
 
     /// The global clock keeps track of how many instructions have been executed through all shards.
     pub global_clk: u64,
@@ -76,6 +227,29 @@ pub struct ExecutionState {
     pub hot_registers: [MemoryRecord; 8],
     // Cold registers (x8-x31) are accessed less frequently
     pub cold_registers: [MemoryRecord; 24],
+
+    /// Machine-mode control/status registers (`mstatus`, `mtvec`, `mepc`,
+    /// `mcause`, `mie`, `mip`, `mtval`, `fcsr`).
+    pub csrs: CsrFile,
+
+    /// Active fork points, innermost last. Not part of the persisted
+    /// program state, so it is skipped when saving/loading.
+    #[serde(skip)]
+    fork_log: Vec<ForkState>,
+
+    /// A lightweight log of fork lifecycle transitions (`fork`/`restore`/
+    /// `commit`), in call order. Not replayed against or restored itself —
+    /// it exists so a caller debugging a long execution (e.g. the benchmark
+    /// `main`) can see where checkpoints were taken and resolved without
+    /// re-running from cycle zero. Skipped when saving/loading, like
+    /// `fork_log`.
+    #[serde(skip)]
+    fork_events: Vec<ForkEvent>,
+
+    /// The 32 floating-point registers `f0..=f31`, holding single-precision
+    /// values NaN-boxed into the low 32 bits per the RISC-V `F`/`D` spec (see
+    /// `get_f32`/`set_f32`/`get_f64`/`set_f64`).
+    pub f_registers: [u64; 32],
 }
 
 impl ExecutionState {
@@ -88,7 +262,7 @@ impl ExecutionState {
             current_shard: 1,
             clk: 0,
             pc: pc_start,
-            memory: Default::default(),
+            memory: Box::default(),
             uninitialized_memory: HashMap::new(),
             input_stream: Vec::new(),
             input_stream_ptr: 0,
@@ -98,9 +272,44 @@ impl ExecutionState {
             syscall_counts: HashMap::new(),
             hot_registers: [MemoryRecord::default(); 8],
             cold_registers: [MemoryRecord::default(); 24],
+            csrs: CsrFile::default(),
+            fork_log: Vec::new(),
+            fork_events: Vec::new(),
+            f_registers: [0; 32],
         }
     }
     
+    /// Read the record at `addr` through the active [`MemoryBackend`].
+    ///
+    /// An address that was never written but has a hint queued in
+    /// [`ExecutionState::uninitialized_memory`] (see `SyscallHintRead`) reads
+    /// back as that hint value rather than the zeroed default.
+    #[must_use]
+    pub fn read_memory(&self, addr: u32) -> MemoryRecord {
+        if !self.memory.is_touched(addr) {
+            if let Some(&value) = self.uninitialized_memory.get(&addr) {
+                return MemoryRecord { value, ..Default::default() };
+            }
+        }
+        self.memory.read(addr)
+    }
+
+    /// Write `record` to `addr` through the active [`MemoryBackend`].
+    ///
+    /// Every write passes through here, which is what lets the fork log
+    /// below capture *every* memory mutation without the executor needing
+    /// to know whether a fork is active.
+    pub fn write_memory(&mut self, addr: u32, record: MemoryRecord) {
+        if !self.fork_log.is_empty() {
+            let prior =
+                if self.memory.is_touched(addr) { Some(self.memory.read(addr)) } else { None };
+            for fork in &mut self.fork_log {
+                fork.memory_diff.entry(addr).or_insert(prior);
+            }
+        }
+        self.memory.write(addr, record);
+    }
+
     // Helper method to get a register value
     pub fn get_register(&self, reg: usize) -> &MemoryRecord {
         if reg < 8 {
@@ -116,7 +325,14 @@ impl ExecutionState {
         if reg == 0 {
             return;
         }
-        
+
+        if !self.fork_log.is_empty() {
+            let prior = *self.get_register(reg);
+            for fork in &mut self.fork_log {
+                fork.register_diff.entry(reg).or_insert(prior);
+            }
+        }
+
         if reg < 8 {
             self.hot_registers[reg] = record;
         } else {
@@ -126,8 +342,10 @@ impl ExecutionState {
 }
 
 /// Holds data to track changes made to the runtime since a fork point.
+///
+/// See [`crate::Executor::fork`]/`restore`/`commit` for how this is used to
+/// checkpoint and roll back execution without replaying from cycle zero.
 #[derive(Debug, Clone, Default)]
-#[allow(dead_code)]
 pub struct ForkState {
     /// The `global_clk` value at the fork point.
     pub global_clk: u64,
@@ -135,16 +353,145 @@ pub struct ForkState {
     pub clk: u32,
     /// The original `pc` value at the fork point.
     pub pc: u32,
-    /// All memory changes since the fork point.
+    /// All memory changes since the fork point, keyed by address, storing
+    /// the *pre-write* record (`None` if the address had never been
+    /// written) the first time each address is touched.
     pub memory_diff: HashMap<u32, Option<MemoryRecord>>,
-    // /// The original memory access record at the fork point.
-    // pub op_record: MemoryAccessRecord,
-    // /// The original execution record at the fork point.
-    // pub record: ExecutionRecord,
+    /// All register changes since the fork point, keyed by register index,
+    /// storing the pre-write value the first time each register is touched.
+    /// Mirrors `memory_diff`; see [`ExecutionState::set_register`].
+    pub register_diff: HashMap<usize, MemoryRecord>,
+    /// The full CSR file at the fork point. `CsrFile` is small and flat (no
+    /// per-register indirection like `memory_diff`/`register_diff` need), so
+    /// it's cheaper to snapshot by value than to diff it, matching how `pc`/
+    /// `clk`/`global_clk` above are already handled.
+    pub csrs: CsrFile,
     /// Whether `emit_events` was enabled at the fork point.
     pub executor_mode: ExecutorMode,
 }
 
+/// A handle to an in-flight fork point created by [`ExecutionState::begin_fork`].
+///
+/// Forks nest like a stack: only the innermost, most recently created handle
+/// may be restored or committed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForkHandle(usize);
+
+/// One entry in [`ExecutionState::fork_events`]: a fork lifecycle transition
+/// and the `global_clk` it happened at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForkEvent {
+    /// [`ExecutionState::begin_fork`] was called.
+    Begin {
+        /// The handle returned to the caller.
+        handle: ForkHandle,
+        /// `global_clk` at the fork point.
+        global_clk: u64,
+    },
+    /// [`ExecutionState::end_fork_restore`] was called.
+    Restore {
+        /// The handle that was restored.
+        handle: ForkHandle,
+        /// `global_clk` at the moment of the restore (before rolling back).
+        global_clk: u64,
+    },
+    /// [`ExecutionState::end_fork_commit`] was called.
+    Commit {
+        /// The handle that was committed.
+        handle: ForkHandle,
+        /// `global_clk` at the moment of the commit.
+        global_clk: u64,
+    },
+}
+
+impl ExecutionState {
+    /// Begin logging every memory mutation from this point on. Returns a
+    /// handle that must later be passed to [`ExecutionState::end_fork_restore`]
+    /// or [`ExecutionState::end_fork_commit`].
+    pub fn begin_fork(&mut self, executor_mode: ExecutorMode) -> ForkHandle {
+        self.fork_log.push(ForkState {
+            global_clk: self.global_clk,
+            clk: self.clk,
+            pc: self.pc,
+            memory_diff: HashMap::new(),
+            register_diff: HashMap::new(),
+            csrs: self.csrs.clone(),
+            executor_mode,
+        });
+        let handle = ForkHandle(self.fork_log.len() - 1);
+        self.fork_events.push(ForkEvent::Begin { handle, global_clk: self.global_clk });
+        handle
+    }
+
+    /// The fork lifecycle transitions recorded so far, in call order.
+    ///
+    /// Purely a debugging aid (e.g. for inspecting a long execution like the
+    /// benchmark `main`) — nothing restores or replays against this.
+    #[must_use]
+    pub fn fork_events(&self) -> &[ForkEvent] {
+        &self.fork_events
+    }
+
+    /// Roll memory, registers, `pc`, and the clocks back to `handle`'s fork
+    /// point by replaying its diff, and return the `executor_mode` that was
+    /// active at the fork point.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `handle` is not the innermost active fork: forks must be
+    /// restored in the reverse order they were created, like `RefCell`
+    /// borrows.
+    pub fn end_fork_restore(&mut self, handle: ForkHandle) -> ExecutorMode {
+        assert_eq!(handle.0, self.fork_log.len() - 1, "forks must be restored in LIFO order");
+        self.fork_events.push(ForkEvent::Restore { handle, global_clk: self.global_clk });
+        let fork = self.fork_log.pop().expect("fork handle outlived its log entry");
+
+        // Each address (resp. register) appears at most once in `memory_diff`
+        // (resp. `register_diff`) — the value it held the first time it was
+        // touched since the fork point — so restoring every entry
+        // reconstructs the fork-point state regardless of iteration order.
+        for (addr, prior) in fork.memory_diff {
+            match prior {
+                Some(record) => self.memory.write(addr, record),
+                // Never written before the fork: zero it and forget the write,
+                // so `read_memory` falls back to any queued hint again.
+                None => {
+                    self.memory.write(addr, MemoryRecord::default());
+                    self.memory.touched.remove(&addr);
+                }
+            }
+        }
+        // Written directly rather than through `set_register`, which would
+        // re-journal these writes into any still-active outer fork — the
+        // same reason the memory restore above calls `self.memory.write`
+        // instead of `self.write_memory`.
+        for (reg, prior) in fork.register_diff {
+            if reg < 8 {
+                self.hot_registers[reg] = prior;
+            } else {
+                self.cold_registers[reg - 8] = prior;
+            }
+        }
+        self.pc = fork.pc;
+        self.clk = fork.clk;
+        self.global_clk = fork.global_clk;
+        self.csrs = fork.csrs;
+        fork.executor_mode
+    }
+
+    /// Discard `handle`'s diff log without rolling anything back.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `handle` is not the innermost active fork (see
+    /// [`ExecutionState::end_fork_restore`]).
+    pub fn end_fork_commit(&mut self, handle: ForkHandle) {
+        assert_eq!(handle.0, self.fork_log.len() - 1, "forks must be committed in LIFO order");
+        self.fork_events.push(ForkEvent::Commit { handle, global_clk: self.global_clk });
+        self.fork_log.pop();
+    }
+}
+
 impl ExecutionState {
     /// Save the execution state to a file.
     pub fn save(&self, file: &mut File) -> std::io::Result<()> {
@@ -155,3 +502,100 @@ impl ExecutionState {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod memory_tests {
+    use super::*;
+
+    #[test]
+    fn read_memory_returns_the_uninitialized_hint_for_an_untouched_address() {
+        let mut state = ExecutionState::new(0);
+        state.uninitialized_memory.insert(8, 0xCAFE_BABE);
+
+        assert_eq!(state.read_memory(8).value, 0xCAFE_BABE);
+    }
+
+    #[test]
+    fn read_memory_ignores_the_hint_once_the_address_has_been_written() {
+        let mut state = ExecutionState::new(0);
+        state.uninitialized_memory.insert(8, 0xCAFE_BABE);
+        state.write_memory(8, MemoryRecord { value: 1, ..Default::default() });
+
+        assert_eq!(state.read_memory(8).value, 1);
+    }
+
+    #[test]
+    fn read_memory_defaults_to_zero_with_no_hint_queued() {
+        let state = ExecutionState::new(0);
+
+        assert_eq!(state.read_memory(8).value, 0);
+    }
+}
+
+#[cfg(test)]
+mod fork_tests {
+    use super::*;
+    use crate::TrapCause;
+
+    #[test]
+    fn restore_reverts_registers_written_since_the_fork_point() {
+        let mut state = ExecutionState::new(0);
+        state.set_register(5, MemoryRecord { value: 11, ..Default::default() });
+
+        let handle = state.begin_fork(ExecutorMode::default());
+        state.set_register(5, MemoryRecord { value: 22, ..Default::default() });
+        state.set_register(20, MemoryRecord { value: 33, ..Default::default() });
+        assert_eq!(state.get_register(5).value, 22);
+        assert_eq!(state.get_register(20).value, 33);
+
+        state.end_fork_restore(handle);
+
+        assert_eq!(state.get_register(5).value, 11, "write inside the fork must revert");
+        assert_eq!(state.get_register(20).value, 0, "never-written register stays at its default");
+    }
+
+    #[test]
+    fn restore_reverts_memory_written_since_the_fork_point() {
+        let mut state = ExecutionState::new(0);
+        state.write_memory(4, MemoryRecord { value: 1, ..Default::default() });
+
+        let handle = state.begin_fork(ExecutorMode::default());
+        state.write_memory(4, MemoryRecord { value: 2, ..Default::default() });
+
+        state.end_fork_restore(handle);
+
+        assert_eq!(state.read_memory(4).value, 1);
+    }
+
+    #[test]
+    fn restore_reverts_csr_writes_since_the_fork_point() {
+        let mut state = ExecutionState::new(0);
+        state.csrs.mstatus = 1;
+
+        let handle = state.begin_fork(ExecutorMode::default());
+        state.csrs.mstatus = 2;
+        state.csrs.mcause = TrapCause::IllegalInstruction.mcause();
+
+        state.end_fork_restore(handle);
+
+        assert_eq!(state.csrs.mstatus, 1, "CSR write inside the fork must revert");
+        assert_eq!(state.csrs.mcause, 0, "CSR never written before the fork stays at its default");
+    }
+
+    #[test]
+    fn commit_keeps_changes_and_records_events_in_call_order() {
+        let mut state = ExecutionState::new(0);
+        let handle = state.begin_fork(ExecutorMode::default());
+        state.set_register(5, MemoryRecord { value: 22, ..Default::default() });
+        state.end_fork_commit(handle);
+
+        assert_eq!(state.get_register(5).value, 22, "commit must not roll back the write");
+        assert_eq!(
+            state.fork_events(),
+            &[
+                ForkEvent::Begin { handle, global_clk: 0 },
+                ForkEvent::Commit { handle, global_clk: 0 },
+            ]
+        );
+    }
+}
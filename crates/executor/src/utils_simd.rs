@@ -4,78 +4,122 @@
 //! - Memory copy
 //! - Memory bulk read
 //! - Memory bulk write
+//!
+//! These operate on a contiguous `&[MemoryRecord]` slice rather than on
+//! [`crate::PagedMemory`] directly; callers obtain one via
+//! [`crate::PagedMemory::page_mut`]/[`crate::PagedMemory::page`] for the page(s)
+//! covering the accessed range.
+//!
+//! Dispatch between the AVX2, SSE2, and scalar paths happens at runtime via
+//! `is_x86_feature_detected!`, since the binary may run on a CPU older than
+//! the one it was compiled on; `build.rs` no longer forces `avx2`/`sse2`
+//! `cfg`s on, so these paths are reachable on any `x86_64` target.
+
+#[allow(clippy::wildcard_imports)]
+use std::arch::x86_64::*;
+use std::mem::size_of;
 
 use crate::events::MemoryRecord;
 
+/// Number of `u32` words between the `value` field of one `MemoryRecord` and
+/// the next, used as the gather/scatter stride.
+const RECORD_STRIDE_WORDS: i32 = (size_of::<MemoryRecord>() / size_of::<u32>()) as i32;
+
 /// Read multiple 32-bit values from memory using SIMD acceleration if available
 ///
 /// # Safety
 ///
 /// This function is unsafe because it reads from potentially unaligned memory addresses
 /// and relies on correct address calculation by the caller.
+#[must_use]
 pub unsafe fn simd_read_memory_values(
     memory: &[MemoryRecord],
     addr: u32,
     size_words: usize,
 ) -> Vec<u32> {
-    let mut values = Vec::with_capacity(size_words);
-    
-    // AVX2 implementation (256-bit registers, 8 x u32 per operation)
-    #[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
-    {
-        let chunks = size_words / 8;
-        let remainder = size_words % 8;
-        
-        for i in 0..chunks {
-            let src_idx = (addr as usize / 4) + i * 8;
-            
-            // Process 8 values at a time
-            for j in 0..8 {
-                values.push(memory[src_idx + j].value);
-            }
-        }
-        
-        // Handle remaining elements
-        let start_idx = chunks * 8;
-        for i in 0..remainder {
-            let src_idx = (addr as usize / 4) + start_idx + i;
-            values.push(memory[src_idx].value);
-        }
-        
-        return values;
-    }
-    
-    // SSE2 implementation (128-bit registers, 4 x u32 per operation)
-    #[cfg(all(target_arch = "x86_64", target_feature = "sse2", not(target_feature = "avx2")))]
-    {
-        let chunks = size_words / 4;
-        let remainder = size_words % 4;
-        
-        for i in 0..chunks {
-            let src_idx = (addr as usize / 4) + i * 4;
-            
-            // Process 4 values at a time
-            for j in 0..4 {
-                values.push(memory[src_idx + j].value);
-            }
-        }
-        
-        // Handle remaining elements
-        let start_idx = chunks * 4;
-        for i in 0..remainder {
-            let src_idx = (addr as usize / 4) + start_idx + i;
-            values.push(memory[src_idx].value);
-        }
-        
-        return values;
+    if is_x86_feature_detected!("avx2") {
+        return unsafe { simd_read_memory_values_avx2(memory, addr, size_words) };
     }
-    
-    // Fallback scalar implementation for non-SIMD platforms
+    if is_x86_feature_detected!("sse2") {
+        return unsafe { simd_read_memory_values_sse2(memory, addr, size_words) };
+    }
+    simd_read_memory_values_scalar(memory, addr, size_words)
+}
+
+fn simd_read_memory_values_scalar(memory: &[MemoryRecord], addr: u32, size_words: usize) -> Vec<u32> {
+    let mut values = Vec::with_capacity(size_words);
+    let base = addr as usize / 4;
     for i in 0..size_words {
-        let src_idx = (addr as usize / 4) + i;
-        values.push(memory[src_idx].value);
+        values.push(memory[base + i].value);
     }
-    
+    values
+}
+
+/// Gather eight `value` fields per instruction with `_mm256_i32gather_epi32`.
+#[target_feature(enable = "avx2")]
+unsafe fn simd_read_memory_values_avx2(memory: &[MemoryRecord], addr: u32, size_words: usize) -> Vec<u32> {
+    let mut values = Vec::with_capacity(size_words);
+    let base = addr as usize / 4;
+
+    let chunks = size_words / 8;
+    let remainder = size_words % 8;
+    let indices = _mm256_setr_epi32(
+        0,
+        RECORD_STRIDE_WORDS,
+        2 * RECORD_STRIDE_WORDS,
+        3 * RECORD_STRIDE_WORDS,
+        4 * RECORD_STRIDE_WORDS,
+        5 * RECORD_STRIDE_WORDS,
+        6 * RECORD_STRIDE_WORDS,
+        7 * RECORD_STRIDE_WORDS,
+    );
+
+    for i in 0..chunks {
+        let ptr = std::ptr::addr_of!(memory[base + i * 8].value).cast::<i32>();
+        let gathered = _mm256_i32gather_epi32::<4>(ptr, indices);
+        let mut lanes = [0i32; 8];
+        _mm256_storeu_si256(lanes.as_mut_ptr().cast(), gathered);
+        values.extend(lanes.iter().map(|&v| v as u32));
+    }
+
+    let start = chunks * 8;
+    for i in 0..remainder {
+        values.push(memory[base + start + i].value);
+    }
+
+    values
+}
+
+/// Collect four `value` fields into a 128-bit register at a time.
+#[target_feature(enable = "sse2")]
+unsafe fn simd_read_memory_values_sse2(memory: &[MemoryRecord], addr: u32, size_words: usize) -> Vec<u32> {
+    // SSE2 has no gather instruction (that arrived with AVX2), so pull the
+    // four strided `value` fields into a register by hand and let the
+    // vectorized store amortize the cost of building the result buffer.
+    let mut values = Vec::with_capacity(size_words);
+    let base = addr as usize / 4;
+
+    let chunks = size_words / 4;
+    let remainder = size_words % 4;
+
+    for i in 0..chunks {
+        let idx = base + i * 4;
+        let packed = _mm_setr_epi32(
+            memory[idx].value as i32,
+            memory[idx + 1].value as i32,
+            memory[idx + 2].value as i32,
+            memory[idx + 3].value as i32,
+        );
+        let mut lanes = [0i32; 4];
+        _mm_storeu_si128(lanes.as_mut_ptr().cast(), packed);
+        values.extend(lanes.iter().map(|&v| v as u32));
+    }
+
+    let start = chunks * 4;
+    for i in 0..remainder {
+        values.push(memory[base + start + i].value);
+    }
+
     values
 }
 
@@ -92,74 +136,102 @@ pub unsafe fn simd_write_memory_values(
     shard: u32,
     timestamp: u32,
 ) {
+    if is_x86_feature_detected!("avx2") {
+        return unsafe { simd_write_memory_values_avx2(memory, addr, values, shard, timestamp) };
+    }
+    if is_x86_feature_detected!("sse2") {
+        return unsafe { simd_write_memory_values_sse2(memory, addr, values, shard, timestamp) };
+    }
+    simd_write_memory_values_scalar(memory, addr, values, shard, timestamp);
+}
+
+fn simd_write_memory_values_scalar(
+    memory: &mut [MemoryRecord],
+    addr: u32,
+    values: &[u32],
+    shard: u32,
+    timestamp: u32,
+) {
+    let base = addr as usize / 4;
+    for (i, &value) in values.iter().enumerate() {
+        memory[base + i].value = value;
+        memory[base + i].shard = shard;
+        memory[base + i].timestamp = timestamp;
+    }
+}
+
+/// Load eight contiguous source values with `_mm256_loadu_si256`, then
+/// scatter them into the strided `MemoryRecord` slots (AVX2 has no scatter
+/// instruction, so the store side stays strided).
+#[target_feature(enable = "avx2")]
+unsafe fn simd_write_memory_values_avx2(
+    memory: &mut [MemoryRecord],
+    addr: u32,
+    values: &[u32],
+    shard: u32,
+    timestamp: u32,
+) {
+    let base = addr as usize / 4;
     let size_words = values.len();
-    
-    // AVX2 implementation (256-bit registers, 8 x u32 per operation)
-    #[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
-    {
-        let chunks = size_words / 8;
-        let remainder = size_words % 8;
-        
-        for i in 0..chunks {
-            let dst_idx = (addr as usize / 4) + i * 8;
-            
-            // Process 8 values at a time
-            for j in 0..8 {
-                let value_idx = i * 8 + j;
-                memory[dst_idx + j].value = values[value_idx];
-                memory[dst_idx + j].shard = shard;
-                memory[dst_idx + j].timestamp = timestamp;
-            }
-        }
-        
-        // Handle remaining elements
-        let start_idx = chunks * 8;
-        for i in 0..remainder {
-            let dst_idx = (addr as usize / 4) + start_idx + i;
-            memory[dst_idx].value = values[start_idx + i];
-            memory[dst_idx].shard = shard;
-            memory[dst_idx].timestamp = timestamp;
-        }
-        
-        return;
-    }
-    
-    // SSE2 implementation (128-bit registers, 4 x u32 per operation)
-    #[cfg(all(target_arch = "x86_64", target_feature = "sse2", not(target_feature = "avx2")))]
-    {
-        let chunks = size_words / 4;
-        let remainder = size_words % 4;
-        
-        for i in 0..chunks {
-            let dst_idx = (addr as usize / 4) + i * 4;
-            
-            // Process 4 values at a time
-            for j in 0..4 {
-                let value_idx = i * 4 + j;
-                memory[dst_idx + j].value = values[value_idx];
-                memory[dst_idx + j].shard = shard;
-                memory[dst_idx + j].timestamp = timestamp;
-            }
+    let chunks = size_words / 8;
+    let remainder = size_words % 8;
+
+    for i in 0..chunks {
+        let loaded = _mm256_loadu_si256(values[i * 8..].as_ptr().cast());
+        let mut lanes = [0i32; 8];
+        _mm256_storeu_si256(lanes.as_mut_ptr().cast(), loaded);
+
+        let idx = base + i * 8;
+        for j in 0..8 {
+            memory[idx + j].value = lanes[j] as u32;
+            memory[idx + j].shard = shard;
+            memory[idx + j].timestamp = timestamp;
         }
-        
-        // Handle remaining elements
-        let start_idx = chunks * 4;
-        for i in 0..remainder {
-            let dst_idx = (addr as usize / 4) + start_idx + i;
-            memory[dst_idx].value = values[start_idx + i];
-            memory[dst_idx].shard = shard;
-            memory[dst_idx].timestamp = timestamp;
+    }
+
+    let start = chunks * 8;
+    for i in 0..remainder {
+        let idx = base + start + i;
+        memory[idx].value = values[start + i];
+        memory[idx].shard = shard;
+        memory[idx].timestamp = timestamp;
+    }
+}
+
+/// Load four contiguous source values with `_mm_loadu_si128`, then scatter
+/// them into the strided `MemoryRecord` slots.
+#[target_feature(enable = "sse2")]
+unsafe fn simd_write_memory_values_sse2(
+    memory: &mut [MemoryRecord],
+    addr: u32,
+    values: &[u32],
+    shard: u32,
+    timestamp: u32,
+) {
+    let base = addr as usize / 4;
+    let size_words = values.len();
+    let chunks = size_words / 4;
+    let remainder = size_words % 4;
+
+    for i in 0..chunks {
+        let loaded = _mm_loadu_si128(values[i * 4..].as_ptr().cast());
+        let mut lanes = [0i32; 4];
+        _mm_storeu_si128(lanes.as_mut_ptr().cast(), loaded);
+
+        let idx = base + i * 4;
+        for j in 0..4 {
+            memory[idx + j].value = lanes[j] as u32;
+            memory[idx + j].shard = shard;
+            memory[idx + j].timestamp = timestamp;
         }
-        
-        return;
     }
-    
-    // Fallback scalar implementation for non-SIMD platforms
-    for i in 0..size_words {
-        let dst_idx = (addr as usize / 4) + i;
-        memory[dst_idx].value = values[i];
-        memory[dst_idx].shard = shard;
-        memory[dst_idx].timestamp = timestamp;
+
+    let start = chunks * 4;
+    for i in 0..remainder {
+        let idx = base + start + i;
+        memory[idx].value = values[start + i];
+        memory[idx].shard = shard;
+        memory[idx].timestamp = timestamp;
     }
 }
 
@@ -176,64 +248,186 @@ pub unsafe fn simd_copy_memory_values(
     dst_addr: u32,
     size_words: usize,
 ) {
-    // AVX2 implementation (256-bit registers, 8 x u32 per operation)
-    #[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
-    {
-        let chunks = size_words / 8;
-        let remainder = size_words % 8;
-        
-        for i in 0..chunks {
-            let src_idx = (src_addr as usize / 4) + i * 8;
-            let dst_idx = (dst_addr as usize / 4) + i * 8;
-            
-            // Copy 8 values at a time
-            for j in 0..8 {
-                dst_memory[dst_idx + j].value = src_memory[src_idx + j].value;
-            }
+    if is_x86_feature_detected!("avx2") {
+        return unsafe { simd_copy_memory_values_avx2(src_memory, dst_memory, src_addr, dst_addr, size_words) };
+    }
+    if is_x86_feature_detected!("sse2") {
+        return unsafe { simd_copy_memory_values_sse2(src_memory, dst_memory, src_addr, dst_addr, size_words) };
+    }
+    simd_copy_memory_values_scalar(src_memory, dst_memory, src_addr, dst_addr, size_words);
+}
+
+fn simd_copy_memory_values_scalar(
+    src_memory: &[MemoryRecord],
+    dst_memory: &mut [MemoryRecord],
+    src_addr: u32,
+    dst_addr: u32,
+    size_words: usize,
+) {
+    let src_base = src_addr as usize / 4;
+    let dst_base = dst_addr as usize / 4;
+    for i in 0..size_words {
+        dst_memory[dst_base + i].value = src_memory[src_base + i].value;
+    }
+}
+
+/// Gather eight source `value` fields per instruction, then scatter them
+/// into the strided destination slots (neither side is packed, since both
+/// are `MemoryRecord` slices rather than a bare `u32` buffer).
+#[target_feature(enable = "avx2")]
+unsafe fn simd_copy_memory_values_avx2(
+    src_memory: &[MemoryRecord],
+    dst_memory: &mut [MemoryRecord],
+    src_addr: u32,
+    dst_addr: u32,
+    size_words: usize,
+) {
+    let src_base = src_addr as usize / 4;
+    let dst_base = dst_addr as usize / 4;
+    let chunks = size_words / 8;
+    let remainder = size_words % 8;
+    let indices = _mm256_setr_epi32(
+        0,
+        RECORD_STRIDE_WORDS,
+        2 * RECORD_STRIDE_WORDS,
+        3 * RECORD_STRIDE_WORDS,
+        4 * RECORD_STRIDE_WORDS,
+        5 * RECORD_STRIDE_WORDS,
+        6 * RECORD_STRIDE_WORDS,
+        7 * RECORD_STRIDE_WORDS,
+    );
+
+    for i in 0..chunks {
+        let src_idx = src_base + i * 8;
+        let dst_idx = dst_base + i * 8;
+
+        let ptr = std::ptr::addr_of!(src_memory[src_idx].value).cast::<i32>();
+        let gathered = _mm256_i32gather_epi32::<4>(ptr, indices);
+        let mut lanes = [0i32; 8];
+        _mm256_storeu_si256(lanes.as_mut_ptr().cast(), gathered);
+
+        for j in 0..8 {
+            dst_memory[dst_idx + j].value = lanes[j] as u32;
         }
-        
-        // Handle remaining elements
-        let start_idx = chunks * 8;
-        for i in 0..remainder {
-            let src_idx = (src_addr as usize / 4) + start_idx + i;
-            let dst_idx = (dst_addr as usize / 4) + start_idx + i;
-            dst_memory[dst_idx].value = src_memory[src_idx].value;
+    }
+
+    let start = chunks * 8;
+    for i in 0..remainder {
+        let src_idx = src_base + start + i;
+        let dst_idx = dst_base + start + i;
+        dst_memory[dst_idx].value = src_memory[src_idx].value;
+    }
+}
+
+/// Collect four source `value` fields into a 128-bit register by hand (SSE2
+/// has no gather), then scatter them into the strided destination slots.
+#[target_feature(enable = "sse2")]
+unsafe fn simd_copy_memory_values_sse2(
+    src_memory: &[MemoryRecord],
+    dst_memory: &mut [MemoryRecord],
+    src_addr: u32,
+    dst_addr: u32,
+    size_words: usize,
+) {
+    let src_base = src_addr as usize / 4;
+    let dst_base = dst_addr as usize / 4;
+    let chunks = size_words / 4;
+    let remainder = size_words % 4;
+
+    for i in 0..chunks {
+        let src_idx = src_base + i * 4;
+        let dst_idx = dst_base + i * 4;
+
+        let packed = _mm_setr_epi32(
+            src_memory[src_idx].value as i32,
+            src_memory[src_idx + 1].value as i32,
+            src_memory[src_idx + 2].value as i32,
+            src_memory[src_idx + 3].value as i32,
+        );
+        let mut lanes = [0i32; 4];
+        _mm_storeu_si128(lanes.as_mut_ptr().cast(), packed);
+
+        for j in 0..4 {
+            dst_memory[dst_idx + j].value = lanes[j] as u32;
         }
-        
-        return;
-    }
-    
-    // SSE2 implementation (128-bit registers, 4 x u32 per operation)
-    #[cfg(all(target_arch = "x86_64", target_feature = "sse2", not(target_feature = "avx2")))]
-    {
-        let chunks = size_words / 4;
-        let remainder = size_words % 4;
-        
-        for i in 0..chunks {
-            let src_idx = (src_addr as usize / 4) + i * 4;
-            let dst_idx = (dst_addr as usize / 4) + i * 4;
-            
-            // Copy 4 values at a time
-            for j in 0..4 {
-                dst_memory[dst_idx + j].value = src_memory[src_idx + j].value;
-            }
+    }
+
+    let start = chunks * 4;
+    for i in 0..remainder {
+        let src_idx = src_base + start + i;
+        let dst_idx = dst_base + start + i;
+        dst_memory[dst_idx].value = src_memory[src_idx].value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Small deterministic PRNG so the test doesn't need an external crate.
+    struct Xorshift(u64);
+
+    impl Xorshift {
+        fn next_u32(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 as u32
+        }
+    }
+
+    fn random_memory(rng: &mut Xorshift, words: usize) -> Vec<MemoryRecord> {
+        (0..words)
+            .map(|_| MemoryRecord {
+                value: rng.next_u32(),
+                shard: rng.next_u32(),
+                timestamp: rng.next_u32(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn simd_read_matches_scalar_for_random_inputs() {
+        let mut rng = Xorshift(0xdead_beef_cafe_f00d);
+        let memory = random_memory(&mut rng, 64);
+
+        for size_words in [0, 1, 3, 4, 7, 8, 15, 16, 33] {
+            let scalar = simd_read_memory_values_scalar(&memory, 0, size_words);
+            let simd = unsafe { simd_read_memory_values(&memory, 0, size_words) };
+            assert_eq!(scalar, simd, "mismatch for size_words={size_words}");
         }
-        
-        // Handle remaining elements
-        let start_idx = chunks * 4;
-        for i in 0..remainder {
-            let src_idx = (src_addr as usize / 4) + start_idx + i;
-            let dst_idx = (dst_addr as usize / 4) + start_idx + i;
-            dst_memory[dst_idx].value = src_memory[src_idx].value;
+    }
+
+    #[test]
+    fn simd_write_matches_scalar_for_random_inputs() {
+        let mut rng = Xorshift(0x1234_5678_9abc_def0);
+
+        for size_words in [0, 1, 3, 4, 7, 8, 15, 16, 33] {
+            let values: Vec<u32> = (0..size_words).map(|_| rng.next_u32()).collect();
+
+            let mut scalar_memory = random_memory(&mut rng, 64);
+            let mut simd_memory = scalar_memory.clone();
+
+            simd_write_memory_values_scalar(&mut scalar_memory, 0, &values, 7, 42);
+            unsafe { simd_write_memory_values(&mut simd_memory, 0, &values, 7, 42) };
+
+            assert_eq!(scalar_memory, simd_memory, "mismatch for size_words={size_words}");
         }
-        
-        return;
     }
-    
-    // Fallback scalar implementation for non-SIMD platforms
-    for i in 0..size_words {
-        let src_idx = (src_addr as usize / 4) + i;
-        let dst_idx = (dst_addr as usize / 4) + i;
-        dst_memory[dst_idx].value = src_memory[src_idx].value;
+
+    #[test]
+    fn simd_copy_matches_scalar_for_random_inputs() {
+        let mut rng = Xorshift(0x0fed_cba9_8765_4321);
+        let src = random_memory(&mut rng, 64);
+
+        for size_words in [0, 1, 3, 4, 7, 8, 15, 16, 33] {
+            let mut scalar_memory = random_memory(&mut rng, 64);
+            let mut simd_memory = scalar_memory.clone();
+
+            simd_copy_memory_values_scalar(&src, &mut scalar_memory, 0, 0, size_words);
+            unsafe { simd_copy_memory_values(&src, &mut simd_memory, 0, 0, size_words) };
+
+            assert_eq!(scalar_memory, simd_memory, "mismatch for size_words={size_words}");
+        }
     }
-}
\ No newline at end of file
+}